@@ -2,7 +2,7 @@ mod v1;
 mod v2;
 mod v3;
 
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Write};
 
 use anyhow::{anyhow, Result};
 
@@ -22,31 +22,52 @@ pub struct Header {
 
 /// A single recording event. Every parsed event is preserved in file order so
 /// timing transforms, selection, and frame generation use the same timeline.
-/// `Other` covers input, resize, exit, and unknown events: their payload is not
-/// modeled, only their timestamp.
+/// `Input` keeps the keys typed, for the keystroke overlay. `Other` covers
+/// exit and unknown events: their payload is not modeled, only their timestamp.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Event {
     Output { time: f64, data: String },
+    Resize { time: f64, cols: usize, rows: usize },
     Marker { time: f64, label: String },
+    Input { time: f64, data: String },
     Other { time: f64 },
 }
 
 impl Event {
     pub fn time(&self) -> f64 {
         match self {
-            Event::Output { time, .. } | Event::Marker { time, .. } | Event::Other { time } => {
-                *time
-            }
+            Event::Output { time, .. }
+            | Event::Resize { time, .. }
+            | Event::Marker { time, .. }
+            | Event::Input { time, .. }
+            | Event::Other { time } => *time,
         }
     }
 
     pub fn with_time(self, time: f64) -> Event {
         match self {
             Event::Output { data, .. } => Event::Output { time, data },
+            Event::Resize { cols, rows, .. } => Event::Resize { time, cols, rows },
             Event::Marker { label, .. } => Event::Marker { time, label },
+            Event::Input { data, .. } => Event::Input { time, data },
             Event::Other { .. } => Event::Other { time },
         }
     }
+
+    /// Whether replaying this event changes terminal state.
+    pub fn is_terminal_change(&self) -> bool {
+        matches!(self, Event::Output { .. } | Event::Resize { .. })
+    }
+}
+
+/// Parse a resize payload in `COLSxROWS` form. Returns `None` for anything
+/// else, including zero dimensions.
+fn parse_term_size(data: &serde_json::Value) -> Option<(usize, usize)> {
+    let (cols, rows) = data.as_str()?.split_once('x')?;
+    let cols: usize = cols.parse().ok()?;
+    let rows: usize = rows.parse().ok()?;
+
+    (cols > 0 && rows > 0).then_some((cols, rows))
 }
 
 impl Default for Header {
@@ -76,3 +97,8 @@ pub fn open<'a, R: BufRead + 'a>(reader: R) -> Result<Asciicast<'a>> {
         v1::load(json).map_err(|_| anyhow!("not a v1, v2, v3 asciicast file"))
     }
 }
+
+/// Write a recording in the asciicast v3 format.
+pub fn write(output: &mut dyn Write, header: &Header, events: &[Event]) -> Result<()> {
+    v3::write(output, header, events)
+}
@@ -3,7 +3,7 @@ use std::io;
 use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Deserializer};
 
-use super::{Asciicast, Event, Header, Theme};
+use super::{parse_term_size, Asciicast, Event, Header, Theme};
 
 #[derive(Deserialize)]
 struct V2Header {
@@ -60,6 +60,19 @@ impl V2EventCode {
                 label: require_string(data, "marker")?,
             }),
 
+            // A malformed resize payload is tolerated like an ignored event: it
+            // stays on the timeline but leaves the terminal size unchanged.
+            V2EventCode::Resize => match parse_term_size(&data) {
+                Some((cols, rows)) => Ok(Event::Resize { time, cols, rows }),
+                None => Ok(Event::Other { time }),
+            },
+
+            // Input that is not a string is tolerated like an ignored event.
+            V2EventCode::Input => match data {
+                serde_json::Value::String(data) => Ok(Event::Input { time, data }),
+                _ => Ok(Event::Other { time }),
+            },
+
             // Ignored events carry no domain payload, so a non-string value is
             // tolerated rather than rejected during parsing.
             _ => Ok(Event::Other { time }),
@@ -231,7 +244,11 @@ mod tests {
                     time: 1.5,
                     data: "b".to_string()
                 },
-                Event::Other { time: 2.0 },
+                Event::Resize {
+                    time: 2.0,
+                    cols: 100,
+                    rows: 40
+                },
             ]
         );
     }
@@ -259,6 +276,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn malformed_resize_payload_is_kept_as_other_event() {
+        let parser = open(r#"{"version":2,"width":80,"height":24}"#).unwrap();
+
+        let lines = ok_lines(vec![
+            r#"[0.5,"r","100"]"#,
+            r#"[1.0,"r","0x40"]"#,
+            r#"[1.5,"r","axb"]"#,
+        ]);
+
+        let events = parser
+            .parse(lines.into_iter())
+            .events
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Other { time: 0.5 },
+                Event::Other { time: 1.0 },
+                Event::Other { time: 1.5 },
+            ]
+        );
+    }
+
     #[test]
     fn rejects_non_string_output_data() {
         let parser = open(r#"{"version":2,"width":80,"height":24}"#).unwrap();
@@ -1,25 +1,28 @@
-use std::io;
+use std::io::{self, Write};
 
 use anyhow::{bail, Context, Result};
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::json;
 
-use super::{Asciicast, Event, Header, Theme};
+use super::{parse_term_size, Asciicast, Event, Header, Theme};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 struct V3Header {
     version: u8,
     term: V3Term,
+    #[serde(skip_serializing_if = "Option::is_none")]
     idle_time_limit: Option<f64>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 struct V3Term {
     cols: u16,
     rows: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
     theme: Option<V3Theme>,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 struct V3Theme {
     #[serde(deserialize_with = "deserialize_color")]
     fg: RGB8,
@@ -66,6 +69,19 @@ impl V3EventCode {
                 label: require_string(data, "marker")?,
             }),
 
+            // A malformed resize payload is tolerated like an ignored event: it
+            // stays on the timeline but leaves the terminal size unchanged.
+            V3EventCode::Resize => match parse_term_size(&data) {
+                Some((cols, rows)) => Ok(Event::Resize { time, cols, rows }),
+                None => Ok(Event::Other { time }),
+            },
+
+            // Input that is not a string is tolerated like an ignored event.
+            V3EventCode::Input => match data {
+                serde_json::Value::String(data) => Ok(Event::Input { time, data }),
+                _ => Ok(Event::Other { time }),
+            },
+
             // Ignored events carry no domain payload, so a non-string value is
             // tolerated rather than rejected during parsing.
             _ => Ok(Event::Other { time }),
@@ -142,6 +158,60 @@ impl Parser {
     }
 }
 
+/// Write `header` and `events` as an asciicast v3 recording. `Other` events
+/// carry no payload to write back, so they are left out.
+pub fn write(output: &mut dyn Write, header: &Header, events: &[Event]) -> Result<()> {
+    let header = V3Header {
+        version: 3,
+        term: V3Term {
+            cols: header.term_cols,
+            rows: header.term_rows,
+            theme: header.term_theme.as_ref().map(V3Theme::from),
+        },
+        idle_time_limit: header.idle_time_limit,
+    };
+
+    writeln!(output, "{}", serde_json::to_string(&header)?)?;
+
+    // Intervals are taken between microsecond-rounded times, so rounding
+    // errors don't accumulate.
+    let mut prev_time = 0;
+
+    for event in events {
+        let (code, data) = match event {
+            Event::Output { data, .. } => ("o", json!(data)),
+            Event::Resize { cols, rows, .. } => ("r", json!(format!("{cols}x{rows}"))),
+            Event::Marker { label, .. } => ("m", json!(label)),
+            Event::Input { data, .. } => ("i", json!(data)),
+            Event::Other { .. } => continue,
+        };
+
+        let time = (event.time() * 1_000_000.0).round() as u64;
+        let interval = time.saturating_sub(prev_time);
+        prev_time = prev_time.max(time);
+
+        writeln!(
+            output,
+            "[{}, \"{code}\", {data}]",
+            format_interval(interval)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Format a microsecond interval as seconds, without trailing zeros.
+fn format_interval(micros: u64) -> String {
+    let fraction = format!("{:06}", micros % 1_000_000);
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        format!("{}.0", micros / 1_000_000)
+    } else {
+        format!("{}.{fraction}", micros / 1_000_000)
+    }
+}
+
 fn deserialize_code<'de, D>(deserializer: D) -> Result<V3EventCode, D::Error>
 where
     D: Deserializer<'de>,
@@ -199,6 +269,34 @@ where
     Ok(V3Palette(colors))
 }
 
+impl Serialize for RGB8 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex_color(self))
+    }
+}
+
+impl Serialize for V3Palette {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let colors: Vec<String> = self.0.iter().map(hex_color).collect();
+
+        serializer.serialize_str(&colors.join(":"))
+    }
+}
+
+fn hex_color(color: &RGB8) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0.r, color.0.g, color.0.b)
+}
+
+impl From<&Theme> for V3Theme {
+    fn from(theme: &Theme) -> Self {
+        V3Theme {
+            fg: RGB8(theme.foreground),
+            bg: RGB8(theme.background),
+            palette: V3Palette(theme.palette.iter().copied().map(RGB8).collect()),
+        }
+    }
+}
+
 impl From<&V3Theme> for Theme {
     fn from(theme: &V3Theme) -> Self {
         let palette = theme.palette.0.iter().map(|c| c.0).collect();
@@ -282,7 +380,11 @@ mod tests {
                     time: 1.5,
                     label: "label".to_string()
                 },
-                Event::Other { time: 1.75 },
+                Event::Resize {
+                    time: 1.75,
+                    cols: 100,
+                    rows: 40
+                },
                 Event::Output {
                     time: 2.0,
                     data: "b".to_string()
@@ -314,6 +416,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn keeps_input_data() {
+        let parser = open(r#"{"version":3,"term":{"cols":80,"rows":24}}"#).unwrap();
+        let lines = ok_lines(vec![r#"[0.5,"i","ls\r"]"#, r#"[0.5,"i",0]"#]);
+
+        let events = parser
+            .parse(lines.into_iter())
+            .events
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Input {
+                    time: 0.5,
+                    data: "ls\r".to_string()
+                },
+                Event::Other { time: 1.0 },
+            ]
+        );
+    }
+
     #[test]
     fn unlabeled_marker_has_empty_label() {
         let parser = open(r#"{"version":3,"term":{"cols":80,"rows":24}}"#).unwrap();
@@ -463,4 +588,54 @@ mod tests {
     fn rejects_non_hex_characters() {
         assert!(parse_hex_color("#gggggg").is_none());
     }
+
+    #[test]
+    fn writes_events_that_parse_back() {
+        let header = Header {
+            term_cols: 10,
+            term_rows: 2,
+            term_theme: Some(
+                "000000,ffffff,000000,111111,222222,333333,444444,555555,666666,777777"
+                    .parse()
+                    .unwrap(),
+            ),
+            idle_time_limit: None,
+        };
+
+        let events = vec![
+            Event::Output {
+                time: 0.0,
+                data: "a\u{1b}[1m\"b\"".to_owned(),
+            },
+            Event::Other { time: 0.5 },
+            Event::Resize {
+                time: 0.1 + 0.2,
+                cols: 20,
+                rows: 3,
+            },
+            Event::Marker {
+                time: 1.25,
+                label: "done".to_owned(),
+            },
+        ];
+
+        let mut output = Vec::new();
+        write(&mut output, &header, &events).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        let mut lines = text.lines();
+
+        assert!(lines.next().unwrap().starts_with(
+            r##"{"version":3,"term":{"cols":10,"rows":2,"theme":{"fg":"#ffffff","bg":"#000000","palette":"#000000:#111111"##
+        ));
+        assert_eq!(lines.next(), Some(r#"[0.0, "o", "a\u001b[1m\"b\""]"#));
+        assert_eq!(lines.next(), Some(r#"[0.3, "r", "20x3"]"#));
+        assert_eq!(lines.next(), Some(r#"[0.95, "m", "done"]"#));
+
+        let asciicast = crate::asciicast::open(text.as_bytes()).unwrap();
+        assert_eq!(asciicast.header.term_theme, header.term_theme);
+
+        let parsed = asciicast.events.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[2].time(), 1.25);
+    }
 }
@@ -0,0 +1,270 @@
+//! Fitting GIF output into a file size limit.
+//!
+//! Frames are rendered once and then encoded in memory at decreasing quality
+//! until the result fits: first with lower gifski qualities, then at lower
+//! frame rates, then scaled down.
+
+use std::fmt::{self, Display};
+
+use anyhow::{bail, Result};
+use imgref::ImgVec;
+use log::info;
+use rgb::RGBA8;
+
+use crate::encoder::{self, Encoder as _};
+use crate::GifQuality;
+
+/// Palette, lossy and motion qualities to step down through.
+const QUALITY_STEPS: [(u8, u8, u8); 5] = [
+    (90, 80, 80),
+    (80, 60, 60),
+    (70, 40, 50),
+    (60, 20, 40),
+    (50, 10, 30),
+];
+
+const FPS_CAP_STEPS: [u8; 3] = [20, 15, 10];
+
+const SCALE_STEPS: [f64; 3] = [0.75, 0.5, 0.35];
+
+/// Settings for one encoding attempt.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attempt {
+    pub quality: GifQuality,
+    pub fps_cap: u8,
+    pub scale: f64,
+}
+
+impl Display for Attempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "--gif-quality {} --gif-lossy-quality {} --gif-motion-quality {} --gif-{} --fps-cap {} --gif-scale {}",
+            self.quality.quality,
+            self.quality.lossy_quality,
+            self.quality.motion_quality,
+            if self.quality.fast { "fast" } else { "slow" },
+            self.fps_cap,
+            self.scale
+        )
+    }
+}
+
+/// The attempts to make, in order, starting with the requested settings. Steps
+/// that would raise any quality, the frame rate or the scale are skipped.
+pub fn attempts(quality: GifQuality, fps_cap: u8, scale: f64) -> Vec<Attempt> {
+    let mut attempt = Attempt {
+        quality,
+        fps_cap,
+        scale,
+    };
+
+    let mut attempts = vec![attempt];
+
+    for (q, lossy, motion) in QUALITY_STEPS {
+        if q < attempt.quality.quality
+            || lossy < attempt.quality.lossy_quality
+            || motion < attempt.quality.motion_quality
+        {
+            attempt.quality.quality = q.min(attempt.quality.quality);
+            attempt.quality.lossy_quality = lossy.min(attempt.quality.lossy_quality);
+            attempt.quality.motion_quality = motion.min(attempt.quality.motion_quality);
+            attempts.push(attempt);
+        }
+    }
+
+    for fps_cap in FPS_CAP_STEPS {
+        if fps_cap < attempt.fps_cap {
+            attempt.fps_cap = fps_cap;
+            attempts.push(attempt);
+        }
+    }
+
+    for scale in SCALE_STEPS {
+        if scale < attempt.scale {
+            attempt.scale = scale;
+            attempts.push(attempt);
+        }
+    }
+
+    attempts
+}
+
+/// Encode `images` as a GIF of at most `max_size` bytes, returning it along
+/// with the settings of the first attempt that fit.
+pub fn fit(
+    images: &[(ImgVec<RGBA8>, f64)],
+    settings: &encoder::Settings,
+    quality: GifQuality,
+    fps_cap: u8,
+    scale: f64,
+    max_size: u64,
+) -> Result<(Vec<u8>, Attempt)> {
+    let mut smallest: Option<(usize, Attempt)> = None;
+
+    for attempt in attempts(quality, fps_cap, scale) {
+        let mut frames = cap_fps(images, attempt.fps_cap);
+
+        let settings = encoder::Settings {
+            width: scaled(settings.width, attempt.scale),
+            height: scaled(settings.height, attempt.scale),
+            frame_count: frames.len() as u64,
+            last_frame_duration: settings.last_frame_duration,
+            loop_count: settings.loop_count,
+            show_progress_bar: false,
+        };
+
+        let mut gif = Vec::new();
+
+        Box::new(encoder::gif(settings, attempt.quality))
+            .encode(&mut frames.drain(..), &mut gif)?;
+
+        info!("{} at {attempt}", format_size(gif.len() as u64));
+
+        if gif.len() as u64 <= max_size {
+            return Ok((gif, attempt));
+        }
+
+        if smallest.is_none_or(|(size, _)| gif.len() < size) {
+            smallest = Some((gif.len(), attempt));
+        }
+    }
+
+    let (size, attempt) = smallest.expect("there is always an attempt");
+
+    bail!(
+        "could not fit the GIF into {}, the smallest was {} at {attempt}",
+        format_size(max_size),
+        format_size(size as u64)
+    );
+}
+
+/// Thin out `images` to at most one per `1/fps_cap` interval, like
+/// `output::cap_fps` does with frames before rendering.
+fn cap_fps(images: &[(ImgVec<RGBA8>, f64)], fps_cap: u8) -> Vec<(ImgVec<RGBA8>, f64)> {
+    let max_frame_time = 1.0 / (fps_cap as f64);
+    let mut windows: Vec<(&ImgVec<RGBA8>, f64)> = Vec::new();
+
+    for (image, time) in images {
+        match windows.last_mut() {
+            Some(window) if time - window.1 < max_frame_time => window.0 = image,
+            _ => windows.push((image, *time)),
+        }
+    }
+
+    windows
+        .into_iter()
+        .map(|(image, time)| (image.clone(), time))
+        .collect()
+}
+
+pub fn scaled(length: usize, scale: f64) -> usize {
+    ((length as f64 * scale).round() as usize).max(1)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= 1_000_000 {
+        format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+    } else if bytes >= 1_000 {
+        format!("{:.1} kB", bytes as f64 / 1_000.0)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(value: u8) -> ImgVec<RGBA8> {
+        ImgVec::new(vec![RGBA8::new(value, value, value, 255)], 1, 1)
+    }
+
+    #[test]
+    fn attempts_step_down_from_requested_settings() {
+        let quality = GifQuality {
+            quality: 75,
+            lossy_quality: 100,
+            motion_quality: 100,
+            fast: true,
+        };
+
+        let attempts = attempts(quality, 15, 1.0);
+
+        assert_eq!(attempts[0].quality, quality);
+        assert_eq!(attempts[0].fps_cap, 15);
+        assert_eq!(attempts[0].scale, 1.0);
+
+        let qualities: Vec<_> = attempts
+            .iter()
+            .map(|a| {
+                (
+                    a.quality.quality,
+                    a.quality.lossy_quality,
+                    a.quality.motion_quality,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            qualities[..6],
+            [
+                (75, 100, 100),
+                (75, 80, 80),
+                (75, 60, 60),
+                (70, 40, 50),
+                (60, 20, 40),
+                (50, 10, 30)
+            ]
+        );
+
+        let fps_caps: Vec<_> = attempts.iter().map(|a| a.fps_cap).collect();
+        assert_eq!(fps_caps[5..], [15, 10, 10, 10, 10]);
+
+        let scales: Vec<_> = attempts.iter().map(|a| a.scale).collect();
+        assert_eq!(scales[6..], [1.0, 0.75, 0.5, 0.35]);
+    }
+
+    #[test]
+    fn attempts_start_from_requested_scale() {
+        let attempts = attempts(crate::GifPreset::default().into(), 10, 0.5);
+        let scales: Vec<_> = attempts.iter().map(|a| a.scale).collect();
+
+        assert_eq!(scales[0], 0.5);
+        assert_eq!(scales[scales.len() - 2..], [0.5, 0.35]);
+    }
+
+    #[test]
+    fn attempt_prints_reusable_options() {
+        let attempt = Attempt {
+            quality: GifQuality {
+                quality: 60,
+                lossy_quality: 20,
+                motion_quality: 40,
+                fast: false,
+            },
+            fps_cap: 15,
+            scale: 0.75,
+        };
+
+        assert_eq!(
+            attempt.to_string(),
+            "--gif-quality 60 --gif-lossy-quality 20 --gif-motion-quality 40 --gif-slow --fps-cap 15 --gif-scale 0.75"
+        );
+    }
+
+    #[test]
+    fn cap_fps_keeps_latest_image_per_interval() {
+        let images = vec![
+            (image(0), 0.0),
+            (image(1), 0.05),
+            (image(2), 0.1),
+            (image(3), 0.5),
+        ];
+
+        let capped = cap_fps(&images, 10);
+        let values: Vec<_> = capped.iter().map(|(i, t)| (i.buf()[0].r, *t)).collect();
+
+        assert_eq!(values, [(1, 0.0), (2, 0.1), (3, 0.5)]);
+    }
+}
@@ -0,0 +1,204 @@
+//! WebVTT chapters and SRT subtitles from markers.
+//!
+//! Cues are placed on the output timeline, the one the rendered frames are
+//! timed on, so they line up with the animation or video.
+
+use std::io::Write;
+
+use anyhow::Result;
+
+/// A marker's label, shown from the marker until the next one or the end.
+/// Unlabeled markers are numbered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Cues for a range selection starting at `start` on the adjusted timeline and
+/// lasting `duration` once rendered. The chapter in progress at the start of
+/// the range begins at 0, and markers past the end are left out.
+pub fn for_range(markers: &[(f64, String)], start: f64, duration: f64) -> Vec<Cue> {
+    let mut times: Vec<(f64, &str)> = Vec::new();
+
+    for (time, label) in markers {
+        let time = time - start;
+
+        if time > duration {
+            break;
+        }
+
+        // A later marker at or before the range start replaces an earlier one.
+        if time <= 0.0 {
+            times.clear();
+        }
+
+        times.push((time.max(0.0), label));
+    }
+
+    cues(times, duration)
+}
+
+/// Cues for a selection of discrete `positions`, shown `frame_duration` each.
+/// A marker's cue starts with the first frame at or after it.
+pub fn for_positions(
+    markers: &[(f64, String)],
+    positions: &[f64],
+    frame_duration: f64,
+) -> Vec<Cue> {
+    let mut times: Vec<(f64, &str)> = Vec::new();
+
+    for (time, label) in markers {
+        let Some(index) = positions.iter().position(|p| p >= time) else {
+            break;
+        };
+
+        let time = index as f64 * frame_duration;
+
+        // Markers sharing a frame share the cue of the last one.
+        if times.last().is_some_and(|(t, _)| *t == time) {
+            times.pop();
+        }
+
+        times.push((time, label));
+    }
+
+    cues(times, positions.len() as f64 * frame_duration)
+}
+
+fn cues(times: Vec<(f64, &str)>, duration: f64) -> Vec<Cue> {
+    let ends = times.iter().skip(1).map(|(t, _)| *t).chain([duration]);
+
+    times
+        .iter()
+        .zip(ends)
+        .enumerate()
+        .filter(|(_, ((start, _), end))| end > start)
+        .map(|(i, ((start, text), end))| Cue {
+            start: *start,
+            end,
+            text: if text.is_empty() {
+                format!("Chapter {}", i + 1)
+            } else {
+                text.to_string()
+            },
+        })
+        .collect()
+}
+
+pub fn write_vtt(output: &mut dyn Write, cues: &[Cue]) -> Result<()> {
+    writeln!(output, "WEBVTT")?;
+
+    for cue in cues {
+        writeln!(output)?;
+
+        writeln!(
+            output,
+            "{} --> {}",
+            timestamp(cue.start, '.'),
+            timestamp(cue.end, '.')
+        )?;
+
+        writeln!(output, "{}", cue.text)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_srt(output: &mut dyn Write, cues: &[Cue]) -> Result<()> {
+    for (i, cue) in cues.iter().enumerate() {
+        if i > 0 {
+            writeln!(output)?;
+        }
+
+        writeln!(output, "{}", i + 1)?;
+
+        writeln!(
+            output,
+            "{} --> {}",
+            timestamp(cue.start, ','),
+            timestamp(cue.end, ',')
+        )?;
+
+        writeln!(output, "{}", cue.text)?;
+    }
+
+    Ok(())
+}
+
+/// `HH:MM:SS.mmm`, with SRT using a comma before the milliseconds.
+fn timestamp(time: f64, separator: char) -> String {
+    let ms = (time * 1000.0).round() as u64;
+
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers() -> Vec<(f64, String)> {
+        vec![
+            (2.0, "setup".to_owned()),
+            (5.0, String::new()),
+            (9.5, "test".to_owned()),
+        ]
+    }
+
+    fn cue(start: f64, end: f64, text: &str) -> Cue {
+        Cue {
+            start,
+            end,
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn range_cues_start_with_the_chapter_in_progress() {
+        let cues = for_range(&markers(), 3.0, 5.0);
+
+        assert_eq!(cues, [cue(0.0, 2.0, "setup"), cue(2.0, 5.0, "Chapter 2")]);
+    }
+
+    #[test]
+    fn position_cues_follow_frame_slots() {
+        let cues = for_positions(&markers(), &[2.0, 5.0, 7.0, 9.5], 3.0);
+
+        assert_eq!(
+            cues,
+            [
+                cue(0.0, 3.0, "setup"),
+                cue(3.0, 9.0, "Chapter 2"),
+                cue(9.0, 12.0, "test")
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_vtt_and_srt() {
+        let cues = [cue(0.0, 61.5, "setup"), cue(61.5, 3725.25, "test")];
+
+        let mut vtt = Vec::new();
+        write_vtt(&mut vtt, &cues).unwrap();
+
+        assert_eq!(
+            String::from_utf8(vtt).unwrap(),
+            "WEBVTT\n\n00:00:00.000 --> 00:01:01.500\nsetup\n\n00:01:01.500 --> 01:02:05.250\ntest\n"
+        );
+
+        let mut srt = Vec::new();
+        write_srt(&mut srt, &cues).unwrap();
+
+        assert_eq!(
+            String::from_utf8(srt).unwrap(),
+            "1\n00:00:00,000 --> 00:01:01,500\nsetup\n\n2\n00:01:01,500 --> 01:02:05,250\ntest\n"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Animation encoding.
+//!
+//! An [`Encoder`] consumes rendered frames in presentation order and writes a
+//! complete animation file. Frame times come from the output pipeline (see
+//! [`crate::output`]) and are shared by every format.
+
+mod apng;
+mod contact_sheet;
+mod frame_dump;
+mod gif;
+mod html;
+mod png;
+mod svg;
+mod transcript;
+#[cfg(feature = "video")]
+mod webm;
+mod webp;
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use crate::chapters::Cue;
+use crate::renderer::ResvgRenderer;
+use crate::GifQuality;
+
+pub trait Encoder: Send {
+    /// Encode `frames`, each a rendered image paired with its start time in
+    /// seconds, into `output`. Every frame is shown until the next one starts;
+    /// the last one for `Settings::last_frame_duration`.
+    fn encode(
+        self: Box<Self>,
+        frames: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()>;
+}
+
+pub struct Settings {
+    pub width: usize,
+    pub height: usize,
+    pub frame_count: u64,
+    pub last_frame_duration: f64,
+    /// Number of times the animation plays, or `None` to loop forever.
+    pub loop_count: Option<u16>,
+    pub show_progress_bar: bool,
+}
+
+pub fn apng(settings: Settings) -> apng::ApngEncoder {
+    apng::ApngEncoder::new(settings)
+}
+
+/// Frames are laid out in a grid of `columns`, or a near-square one when
+/// `None`. `captions` go under the frames with matching indexes.
+pub fn contact_sheet(
+    settings: Settings,
+    columns: Option<usize>,
+    captions: Vec<ImgVec<RGBA8>>,
+) -> contact_sheet::ContactSheetEncoder {
+    contact_sheet::ContactSheetEncoder::new(settings, columns, captions)
+}
+
+/// Like the transcript encoder, the frame dump encoder takes terminal snapshots.
+/// `cols` and `rows` are the canvas size in cells.
+pub fn frame_dump(
+    cols: usize,
+    rows: usize,
+    last_frame_duration: f64,
+) -> frame_dump::FrameDumpEncoder {
+    frame_dump::FrameDumpEncoder::new(cols, rows, last_frame_duration)
+}
+
+pub fn gif(settings: Settings, quality: GifQuality) -> gif::GifEncoder {
+    gif::GifEncoder::new(settings, quality)
+}
+
+/// A page playing the frames, with `chapters` to jump to.
+pub fn html(settings: Settings, chapters: Vec<Cue>) -> html::HtmlEncoder {
+    html::HtmlEncoder::new(settings, chapters)
+}
+
+pub fn png() -> png::PngEncoder {
+    png::PngEncoder::new(None)
+}
+
+/// The frame at `index` of an animation, as a still image.
+pub fn png_still(index: usize) -> png::PngEncoder {
+    png::PngEncoder::new(Some(index))
+}
+
+/// Frame images go into `dir`; the output receives the manifest.
+pub fn png_sequence(settings: Settings, dir: PathBuf) -> png::PngSequenceEncoder {
+    png::PngSequenceEncoder::new(settings, dir)
+}
+
+/// Unlike the other encoders, which take rendered images, the SVG encoder takes
+/// terminal snapshots and lays them out with the resvg renderer's markup.
+pub fn svg(settings: Settings, renderer: ResvgRenderer) -> svg::SvgEncoder {
+    svg::SvgEncoder::new(settings, renderer)
+}
+
+/// Like the SVG encoder, the transcript encoder takes terminal snapshots.
+/// `ansi` keeps text attributes as SGR sequences.
+pub fn transcript(ansi: bool) -> transcript::TranscriptEncoder {
+    transcript::TranscriptEncoder::new(ansi)
+}
+
+/// Video at a constant `fps`, holding each frame until the next one starts.
+#[cfg(feature = "video")]
+pub fn webm(settings: Settings, fps: u8) -> webm::WebmEncoder {
+    webm::WebmEncoder::new(settings, fps)
+}
+
+/// `quality` selects lossy encoding at the given 0..=100 quality; `None` encodes
+/// losslessly.
+pub fn webp(settings: Settings, quality: Option<f32>) -> webp::WebpEncoder {
+    webp::WebpEncoder::new(settings, quality)
+}
+
+fn progress_bar(settings: &Settings) -> Option<gifski::progress::ProgressBar<io::Stdout>> {
+    settings
+        .show_progress_bar
+        .then(|| gifski::progress::ProgressBar::new(settings.frame_count))
+}
+
+fn finish_progress_bar(progress_bar: Option<gifski::progress::ProgressBar<io::Stdout>>) {
+    if let Some(mut pb) = progress_bar {
+        pb.finish();
+        println!();
+    }
+}
+
+/// Canvas area that changed since the previous frame. `x` and `y` are even, as
+/// required by WebP frame offsets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+fn full_rect(image: &ImgVec<RGBA8>) -> Rect {
+    Rect {
+        x: 0,
+        y: 0,
+        width: image.width(),
+        height: image.height(),
+    }
+}
+
+/// The bounding box of pixels that differ between `prev` and `next`, grown to
+/// an even origin. Returns `None` when the images are identical.
+fn changed_rect(prev: &ImgVec<RGBA8>, next: &ImgVec<RGBA8>) -> Option<Rect> {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+
+    for (y, (prev_row, next_row)) in prev.rows().zip(next.rows()).enumerate() {
+        let Some(first) = prev_row.iter().zip(next_row).position(|(a, b)| a != b) else {
+            continue;
+        };
+
+        let last = prev_row
+            .iter()
+            .zip(next_row)
+            .rposition(|(a, b)| a != b)
+            .unwrap();
+
+        bounds = Some(match bounds {
+            None => (first, y, last, y),
+            Some((x0, y0, x1, _)) => (x0.min(first), y0, x1.max(last), y),
+        });
+    }
+
+    bounds.map(|(x0, y0, x1, y1)| {
+        let x = x0 & !1;
+        let y = y0 & !1;
+
+        Rect {
+            x,
+            y,
+            width: x1 + 1 - x,
+            height: y1 + 1 - y,
+        }
+    })
+}
+
+/// Packed RGB bytes of the `rect` area of `image`, dropping the alpha channel of
+/// the always opaque rendered frames.
+fn rgb_bytes(image: &ImgVec<RGBA8>, rect: Rect) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(rect.width * rect.height * 3);
+
+    for row in image
+        .sub_image(rect.x, rect.y, rect.width, rect.height)
+        .rows()
+    {
+        rgb.extend(row.iter().flat_map(|p| [p.r, p.g, p.b]));
+    }
+
+    rgb
+}
+
+/// Duration between two frame start times in whole milliseconds. Both times are
+/// rounded first, so rounding errors don't accumulate over the animation.
+fn duration_ms(start: f64, end: f64) -> u64 {
+    let start = (start * 1000.0).round() as u64;
+    let end = (end * 1000.0).round() as u64;
+
+    end.saturating_sub(start)
+}
+
+/// A gray image with rows of `width` pixels of the given values.
+#[cfg(test)]
+pub(crate) fn image(pixels: &[u8], width: usize) -> ImgVec<RGBA8> {
+    let buf = pixels.iter().map(|&v| RGBA8::new(v, v, v, 255)).collect();
+
+    ImgVec::new(buf, width, pixels.len() / width)
+}
+
+/// Settings for a single frame animation that loops forever, without a
+/// progress bar.
+#[cfg(test)]
+pub(crate) fn settings(width: usize, height: usize) -> Settings {
+    Settings {
+        width,
+        height,
+        frame_count: 1,
+        last_frame_duration: 1.0,
+        loop_count: None,
+        show_progress_bar: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_rect_covers_differing_pixels_from_an_even_origin() {
+        let prev = image(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 4);
+        let next = image(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0], 4);
+
+        assert_eq!(
+            changed_rect(&prev, &next),
+            Some(Rect {
+                x: 2,
+                y: 0,
+                width: 2,
+                height: 2
+            })
+        );
+
+        assert_eq!(changed_rect(&prev, &prev), None);
+    }
+
+    #[test]
+    fn durations_do_not_accumulate_rounding_errors() {
+        let times = [0.0, 0.0334, 0.0668, 0.1002];
+
+        let total: u64 = times.windows(2).map(|w| duration_ms(w[0], w[1])).sum();
+
+        assert_eq!(total, 100);
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::Write;
+
+use anyhow::Result;
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use super::{changed_rect, duration_ms, full_rect, rgb_bytes, Encoder, Rect, Settings};
+
+pub struct ApngEncoder {
+    settings: Settings,
+}
+
+impl ApngEncoder {
+    pub fn new(settings: Settings) -> Self {
+        Self { settings }
+    }
+}
+
+impl Encoder for ApngEncoder {
+    fn encode(
+        self: Box<Self>,
+        frames: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()> {
+        let settings = &self.settings;
+        let mut progress_bar = super::progress_bar(settings);
+
+        let mut encoder = png::Encoder::new(output, settings.width as u32, settings.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Best);
+        encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+
+        let num_plays = settings.loop_count.map_or(0, u32::from);
+        encoder.set_animated(settings.frame_count as u32, num_plays)?;
+
+        let mut writer = encoder.write_header()?;
+
+        // The previous frame is written once the next one's start time, and so
+        // its delay, is known.
+        let mut pending: Option<(ImgVec<RGBA8>, Rect, f64)> = None;
+
+        for (image, time) in frames {
+            if let Some(pb) = progress_bar.as_mut() {
+                pb.inc();
+            }
+
+            let rect = match pending.take() {
+                Some((prev, rect, prev_time)) => {
+                    write_frame(&mut writer, &prev, rect, duration_ms(prev_time, time))?;
+
+                    // APNG frames can't be empty, so an unchanged frame repeats
+                    // a single pixel.
+                    changed_rect(&prev, &image).unwrap_or(Rect {
+                        x: 0,
+                        y: 0,
+                        width: 1,
+                        height: 1,
+                    })
+                }
+
+                None => full_rect(&image),
+            };
+
+            pending = Some((image, rect, time));
+        }
+
+        if let Some((image, rect, time)) = pending {
+            let duration = duration_ms(time, time + settings.last_frame_duration);
+            write_frame(&mut writer, &image, rect, duration)?;
+        }
+
+        writer.finish()?;
+        super::finish_progress_bar(progress_bar);
+
+        Ok(())
+    }
+}
+
+/// Write the `rect` area of `image` as the next animation frame, replacing the
+/// canvas pixels under it.
+fn write_frame<W: Write>(
+    writer: &mut png::Writer<W>,
+    image: &ImgVec<RGBA8>,
+    rect: Rect,
+    duration: u64,
+) -> Result<()> {
+    let (delay_num, delay_den) = frame_delay(duration);
+
+    writer.set_frame_delay(delay_num, delay_den)?;
+    writer.reset_frame_position()?;
+    writer.set_frame_dimension(rect.width as u32, rect.height as u32)?;
+    writer.set_frame_position(rect.x as u32, rect.y as u32)?;
+    writer.set_blend_op(png::BlendOp::Source)?;
+    writer.set_dispose_op(png::DisposeOp::None)?;
+    writer.write_image_data(&rgb_bytes(image, rect))?;
+
+    Ok(())
+}
+
+/// Express a duration in milliseconds as an fcTL delay fraction. Durations that
+/// overflow a millisecond numerator fall back to centiseconds.
+fn frame_delay(duration: u64) -> (u16, u16) {
+    if let Ok(ms) = u16::try_from(duration) {
+        (ms, 1000)
+    } else {
+        (((duration + 5) / 10).min(u16::MAX as u64) as u16, 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{image, settings};
+
+    #[test]
+    fn long_delays_fall_back_to_centiseconds() {
+        assert_eq!(frame_delay(40), (40, 1000));
+        assert_eq!(frame_delay(65_535), (65_535, 1000));
+        assert_eq!(frame_delay(90_004), (9_000, 100));
+        assert_eq!(frame_delay(u64::MAX / 2), (u16::MAX, 100));
+    }
+
+    #[test]
+    fn encodes_every_frame_including_unchanged_ones() {
+        let settings = Settings {
+            frame_count: 3,
+            ..settings(4, 2)
+        };
+
+        let frames = vec![
+            (image(&[0; 8], 4), 0.0),
+            (image(&[0; 8], 4), 0.5),
+            (image(&[0, 0, 0, 7, 0, 0, 0, 0], 4), 1.5),
+        ];
+
+        let mut output = Vec::new();
+
+        Box::new(ApngEncoder::new(settings))
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        let decoder = png::Decoder::new(output.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+
+        assert_eq!((info.width, info.height), (4, 2));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+
+        let animation = info.animation_control.unwrap();
+        assert_eq!(animation.num_frames, 3);
+        assert_eq!(animation.num_plays, 0);
+    }
+}
@@ -0,0 +1,171 @@
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use super::{png::write_image, Encoder, Settings};
+
+/// Space between tiles and around the grid, in pixels.
+const GUTTER: usize = 12;
+
+/// Writes every frame as a tile of a grid on a single PNG image, each tile
+/// optionally followed by a caption image.
+pub struct ContactSheetEncoder {
+    settings: Settings,
+    columns: Option<usize>,
+    captions: Vec<ImgVec<RGBA8>>,
+}
+
+impl ContactSheetEncoder {
+    pub fn new(settings: Settings, columns: Option<usize>, captions: Vec<ImgVec<RGBA8>>) -> Self {
+        Self {
+            settings,
+            columns,
+            captions,
+        }
+    }
+}
+
+impl Encoder for ContactSheetEncoder {
+    fn encode(
+        self: Box<Self>,
+        frames: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()> {
+        let settings = &self.settings;
+        let mut progress_bar = super::progress_bar(settings);
+        let mut tiles = Vec::new();
+
+        for (image, _) in frames {
+            if let Some(pb) = progress_bar.as_mut() {
+                pb.inc();
+            }
+
+            tiles.push(image);
+        }
+
+        let Some(first) = tiles.first() else {
+            bail!("no frame to write");
+        };
+
+        let columns = self
+            .columns
+            .unwrap_or_else(|| (tiles.len() as f64).sqrt().ceil() as usize)
+            .clamp(1, tiles.len());
+
+        let rows = tiles.len().div_ceil(columns);
+        let caption_height = self.captions.iter().map(|c| c.height()).max().unwrap_or(0);
+        let cell_width = settings.width + GUTTER;
+        let cell_height = settings.height + caption_height + GUTTER;
+
+        // The gutter is a darker shade of the terminal background, which is
+        // what the frame margins show.
+        let background = first.buf()[0];
+        let gutter = RGBA8::new(background.r / 2, background.g / 2, background.b / 2, 255);
+
+        let mut sheet = ImgVec::new(
+            vec![gutter; (columns * cell_width + GUTTER) * (rows * cell_height + GUTTER)],
+            columns * cell_width + GUTTER,
+            rows * cell_height + GUTTER,
+        );
+
+        for (i, tile) in tiles.iter().enumerate() {
+            let x = GUTTER + (i % columns) * cell_width;
+            let y = GUTTER + (i / columns) * cell_height;
+
+            fill(
+                &mut sheet,
+                x,
+                y,
+                settings.width,
+                settings.height + caption_height,
+                background,
+            );
+            blit(&mut sheet, tile, x, y);
+
+            if let Some(caption) = self.captions.get(i) {
+                blit(&mut sheet, caption, x, y + settings.height);
+            }
+        }
+
+        write_image(&sheet, output)?;
+        super::finish_progress_bar(progress_bar);
+
+        Ok(())
+    }
+}
+
+fn fill(image: &mut ImgVec<RGBA8>, x: usize, y: usize, width: usize, height: usize, color: RGBA8) {
+    for row in image.sub_image_mut(x, y, width, height).rows_mut() {
+        row.fill(color);
+    }
+}
+
+/// Copy `src` onto `dst` with its top-left corner at `x`, `y`, clipped to the
+/// bounds of `dst`.
+fn blit(dst: &mut ImgVec<RGBA8>, src: &ImgVec<RGBA8>, x: usize, y: usize) {
+    let width = src.width().min(dst.width().saturating_sub(x));
+    let height = src.height().min(dst.height().saturating_sub(y));
+    let mut area = dst.sub_image_mut(x, y, width, height);
+
+    for (dst_row, src_row) in area.rows_mut().zip(src.rows()) {
+        dst_row.copy_from_slice(&src_row[..width]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::settings;
+
+    fn image(value: u8, width: usize, height: usize) -> ImgVec<RGBA8> {
+        ImgVec::new(
+            vec![RGBA8::new(value, value, value, 255); width * height],
+            width,
+            height,
+        )
+    }
+
+    #[test]
+    fn lays_tiles_out_in_a_captioned_grid() {
+        let settings = Settings {
+            frame_count: 3,
+            ..settings(4, 2)
+        };
+
+        let frames = vec![
+            (image(100, 4, 2), 0.0),
+            (image(100, 4, 2), 1.0),
+            (image(100, 4, 2), 2.0),
+        ];
+
+        let captions = vec![image(200, 4, 1), image(200, 4, 1), image(200, 4, 1)];
+        let mut output = Vec::new();
+
+        Box::new(ContactSheetEncoder::new(settings, None, captions))
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        let decoder = png::Decoder::new(output.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+
+        // Two columns and two rows of 4x3 cells, with gutters.
+        assert_eq!(info.width as usize, 2 * 4 + 3 * GUTTER);
+        assert_eq!(info.height as usize, 2 * 3 + 3 * GUTTER);
+
+        let pixel = |x: usize, y: usize| buf[(y * info.width as usize + x) * 3];
+
+        assert_eq!(pixel(0, 0), 50);
+        assert_eq!(pixel(GUTTER, GUTTER), 100);
+        assert_eq!(pixel(GUTTER, GUTTER + 2), 200);
+        assert_eq!(pixel(2 * GUTTER + 4, GUTTER), 100);
+        assert_eq!(pixel(GUTTER, 2 * GUTTER + 3), 100);
+        assert_eq!(pixel(GUTTER, 2 * GUTTER + 5), 200);
+
+        // The empty fourth cell shows the gutter color.
+        assert_eq!(pixel(2 * GUTTER + 4, 2 * GUTTER + 3), 50);
+    }
+}
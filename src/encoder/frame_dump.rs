@@ -0,0 +1,208 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde::{Serialize, Serializer};
+
+use crate::terminal::Snapshot;
+
+/// Writes the terminal state of every frame as JSON: its start time, the cursor
+/// position and the cells of each line with their pen attributes. Frames are
+/// the same the image encoders receive, so tests can check what an animation
+/// shows without decoding it.
+pub struct FrameDumpEncoder {
+    cols: usize,
+    rows: usize,
+    last_frame_duration: f64,
+}
+
+#[derive(Serialize)]
+struct Dump {
+    cols: usize,
+    rows: usize,
+    last_frame_duration: f64,
+    frames: Vec<Frame>,
+}
+
+#[derive(Serialize)]
+struct Frame {
+    time: f64,
+    /// `[col, row]`, or `None` when the cursor is hidden.
+    cursor: Option<(usize, usize)>,
+    lines: Vec<Vec<Cell>>,
+}
+
+/// A cell with its attributes; unset ones are left out to keep the dump small.
+#[derive(Serialize)]
+struct Cell {
+    /// The character, or the whole cluster of more than one.
+    char: String,
+    /// 2 for the first cell of a wide character, 0 for the one it covers.
+    width: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fg: Option<Color>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bg: Option<Color>,
+    #[serde(skip_serializing_if = "is_false")]
+    bold: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    faint: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    italic: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    underline: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    strikethrough: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    blink: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    inverse: bool,
+}
+
+/// A palette index as a number, or a true color as an `#rrggbb` string.
+struct Color(avt::Color);
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            avt::Color::Indexed(n) => serializer.serialize_u8(n),
+            avt::Color::RGB(c) => {
+                serializer.serialize_str(&format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b))
+            }
+        }
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl FrameDumpEncoder {
+    pub fn new(cols: usize, rows: usize, last_frame_duration: f64) -> Self {
+        Self {
+            cols,
+            rows,
+            last_frame_duration,
+        }
+    }
+
+    /// Encode `frames`, each a terminal snapshot paired with its start time in
+    /// seconds, into `output`.
+    pub fn encode(
+        self,
+        frames: &mut dyn Iterator<Item = (Snapshot, f64)>,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        let dump = Dump {
+            cols: self.cols,
+            rows: self.rows,
+            last_frame_duration: self.last_frame_duration,
+            frames: frames
+                .map(|(snapshot, time)| Frame {
+                    time,
+                    cursor: snapshot.cursor,
+                    lines: snapshot
+                        .lines
+                        .iter()
+                        .enumerate()
+                        .map(|(row, line)| {
+                            line.cells()
+                                .iter()
+                                .enumerate()
+                                .map(|(col, c)| cell(c, snapshot.grapheme(col, row)))
+                                .collect()
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+
+        serde_json::to_writer(&mut *output, &dump)?;
+        writeln!(output)?;
+
+        Ok(())
+    }
+}
+
+fn cell(cell: &avt::Cell, cluster: Option<&str>) -> Cell {
+    let pen = cell.pen();
+
+    Cell {
+        char: cluster.map_or_else(|| cell.char().to_string(), str::to_owned),
+        width: cell.width(),
+        fg: pen.foreground().map(Color),
+        bg: pen.background().map(Color),
+        bold: pen.is_bold(),
+        faint: pen.is_faint(),
+        italic: pen.is_italic(),
+        underline: pen.is_underline(),
+        strikethrough: pen.is_strikethrough(),
+        blink: pen.is_blink(),
+        inverse: pen.is_inverse(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dumps_cells_with_pen_attributes() {
+        let mut vt = avt::Vt::new(3, 2);
+        vt.feed_str("\x1b[1;31ma\x1b[0;4;48;2;1;2;255m好\x1b[0m\r\n");
+        let frames = vec![(Snapshot::from_vt(&vt), 0.5)];
+        let mut output = Vec::new();
+
+        FrameDumpEncoder::new(3, 2, 1.0)
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        let dump: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(
+            dump,
+            serde_json::json!({
+                "cols": 3,
+                "rows": 2,
+                "last_frame_duration": 1.0,
+                "frames": [{
+                    "time": 0.5,
+                    "cursor": [0, 1],
+                    "lines": [
+                        [
+                            {"char": "a", "width": 1, "fg": 1, "bold": true},
+                            {"char": "好", "width": 2, "bg": "#0102ff", "underline": true},
+                            {"char": " ", "width": 0, "bg": "#0102ff", "underline": true}
+                        ],
+                        [
+                            {"char": " ", "width": 1},
+                            {"char": " ", "width": 1},
+                            {"char": " ", "width": 1}
+                        ]
+                    ]
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn dumps_grapheme_clusters_whole() {
+        let mut terminal = crate::terminal::build((2, 1));
+        crate::terminal::feed_str(&mut terminal, "e\u{301}x");
+        let frames = vec![(Snapshot::new(&terminal), 0.0)];
+        let mut output = Vec::new();
+
+        FrameDumpEncoder::new(2, 1, 1.0)
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        let dump: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(
+            dump["frames"][0]["lines"][0],
+            serde_json::json!([
+                {"char": "e\u{301}", "width": 1},
+                {"char": "x", "width": 1}
+            ])
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::io::Write;
+use std::thread;
+
+use anyhow::Result;
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use super::{Encoder, Settings};
+use crate::GifQuality;
+
+pub struct GifEncoder {
+    settings: Settings,
+    quality: GifQuality,
+}
+
+impl GifEncoder {
+    pub fn new(settings: Settings, quality: GifQuality) -> Self {
+        Self { settings, quality }
+    }
+}
+
+impl Encoder for GifEncoder {
+    fn encode(
+        self: Box<Self>,
+        frames: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()> {
+        let settings = self.settings;
+        let quality = self.quality;
+
+        // GIF counts the repeats after the first play.
+        let repeat = match settings.loop_count {
+            Some(plays) => gifski::Repeat::Finite(plays - 1),
+            None => gifski::Repeat::Infinite,
+        };
+
+        let gifski_settings = gifski::Settings {
+            width: Some(settings.width as u32),
+            height: Some(settings.height as u32),
+            quality: quality.quality,
+            fast: quality.fast,
+            repeat,
+        };
+
+        let (collector, mut writer) = gifski::new(gifski_settings)?;
+
+        // These settings have no place in gifski::Settings yet.
+        #[allow(deprecated)]
+        {
+            writer.set_lossy_quality(quality.lossy_quality);
+            writer.set_motion_quality(quality.motion_quality);
+            writer.set_extra_effort(!quality.fast);
+        }
+
+        thread::scope(|s| {
+            let writer_handle = s.spawn(move || {
+                if settings.show_progress_bar {
+                    let mut pr = gifski::progress::ProgressBar::new(settings.frame_count);
+                    let result = writer.write(output, &mut pr);
+                    pr.finish();
+                    println!();
+                    result
+                } else {
+                    let mut pr = gifski::progress::NoProgress {};
+                    writer.write(output, &mut pr)
+                }
+            });
+
+            // gifski derives the last frame's delay from the first frame's
+            // timestamp, so every frame is shifted by the last frame duration.
+            for (i, (image, time)) in frames.enumerate() {
+                collector.add_frame_rgba(i, image, time + settings.last_frame_duration)?;
+            }
+
+            drop(collector);
+            writer_handle.join().unwrap()?;
+            Result::<()>::Ok(())
+        })
+    }
+}
@@ -0,0 +1,340 @@
+use std::io::Write;
+
+use anyhow::Result;
+use base64::Engine as _;
+use imgref::ImgVec;
+use rgb::RGBA8;
+use serde::Serialize;
+
+use super::{png::write_rect, Encoder, Rect, Settings};
+use crate::chapters::Cue;
+
+/// Every this many frames the full image is stored, so seeking never redraws
+/// more than this many frames.
+const KEY_FRAME_INTERVAL: usize = 30;
+
+/// Writes a single HTML page with a canvas player. Frames are inline PNG images
+/// of the area that changed since the previous frame, drawn over each other, so
+/// the page needs nothing but itself to play.
+pub struct HtmlEncoder {
+    settings: Settings,
+    chapters: Vec<Cue>,
+}
+
+#[derive(Serialize)]
+struct Player<'a> {
+    width: usize,
+    height: usize,
+    duration: f64,
+    loop_count: Option<u16>,
+    frames: Vec<Frame>,
+    chapters: Vec<Chapter<'a>>,
+}
+
+#[derive(Serialize)]
+struct Frame {
+    time: f64,
+    x: usize,
+    y: usize,
+    key: bool,
+    /// A PNG data URL, or `None` when nothing changed.
+    src: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Chapter<'a> {
+    time: f64,
+    label: &'a str,
+}
+
+impl HtmlEncoder {
+    pub fn new(settings: Settings, chapters: Vec<Cue>) -> Self {
+        Self { settings, chapters }
+    }
+}
+
+impl Encoder for HtmlEncoder {
+    fn encode(
+        self: Box<Self>,
+        frames: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()> {
+        let settings = &self.settings;
+        let mut progress_bar = super::progress_bar(settings);
+        let mut prev: Option<ImgVec<RGBA8>> = None;
+        let mut player_frames = Vec::new();
+        let mut last_time = 0.0;
+
+        for (i, (image, time)) in frames.enumerate() {
+            if let Some(pb) = progress_bar.as_mut() {
+                pb.inc();
+            }
+
+            let key = i % KEY_FRAME_INTERVAL == 0;
+
+            let rect = match &prev {
+                Some(prev) if !key => super::changed_rect(prev, &image),
+                _ => Some(super::full_rect(&image)),
+            };
+
+            let frame = match rect {
+                Some(rect) => Frame {
+                    time,
+                    x: rect.x,
+                    y: rect.y,
+                    key,
+                    src: Some(data_url(&image, rect)?),
+                },
+
+                None => Frame {
+                    time,
+                    x: 0,
+                    y: 0,
+                    key,
+                    src: None,
+                },
+            };
+
+            player_frames.push(frame);
+            last_time = time;
+            prev = Some(image);
+        }
+
+        let player = Player {
+            width: settings.width,
+            height: settings.height,
+            duration: last_time + settings.last_frame_duration,
+            loop_count: settings.loop_count,
+            frames: player_frames,
+            chapters: self
+                .chapters
+                .iter()
+                .map(|cue| Chapter {
+                    time: cue.start,
+                    label: &cue.text,
+                })
+                .collect(),
+        };
+
+        // A "</script>" in a marker label would end the script element early.
+        let data = serde_json::to_string(&player)?.replace("</", "<\\/");
+
+        write!(
+            output,
+            "{}",
+            TEMPLATE
+                .replace("{width}", &settings.width.to_string())
+                .replace("{height}", &settings.height.to_string())
+                .replace("{data}", &data)
+        )?;
+
+        super::finish_progress_bar(progress_bar);
+
+        Ok(())
+    }
+}
+
+fn data_url(image: &ImgVec<RGBA8>, rect: Rect) -> Result<String> {
+    let mut png = Vec::new();
+    write_rect(image, rect, &mut png)?;
+
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png)
+    ))
+}
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>agg</title>
+<style>
+.agg-player { display: inline-block; max-width: 100%; font: 14px system-ui, sans-serif; color: #ddd; background: #222; border-radius: 6px; overflow: hidden; }
+.agg-player canvas { display: block; max-width: 100%; height: auto; }
+.agg-controls { display: flex; align-items: center; gap: 8px; padding: 6px 8px; }
+.agg-controls input { flex: 1; }
+.agg-controls span { font-variant-numeric: tabular-nums; white-space: nowrap; }
+.agg-player button { font: inherit; color: inherit; background: #333; border: 1px solid #555; border-radius: 4px; padding: 2px 8px; cursor: pointer; }
+.agg-chapters { display: flex; flex-wrap: wrap; gap: 4px; margin: 0; padding: 0 8px 8px; list-style: none; }
+.agg-chapters button.current { background: #555; }
+</style>
+</head>
+<body>
+<div class="agg-player">
+<canvas id="agg-screen" width="{width}" height="{height}"></canvas>
+<div class="agg-controls">
+<button id="agg-toggle" type="button">Play</button>
+<input id="agg-scrub" type="range" min="0" step="0.01" value="0">
+<span id="agg-time"></span>
+</div>
+<ol id="agg-chapters" class="agg-chapters"></ol>
+</div>
+<script>
+(() => {
+  const player = {data};
+  const frames = player.frames;
+  const ctx = document.getElementById("agg-screen").getContext("2d");
+  const toggle = document.getElementById("agg-toggle");
+  const scrub = document.getElementById("agg-scrub");
+  const clock = document.getElementById("agg-time");
+  const images = frames.map((f) => f.src && Object.assign(new Image(), { src: f.src }));
+  const chapterButtons = [];
+  let drawn = -1;
+  let time = 0;
+  let origin = 0;
+  let plays = 0;
+  let playing = false;
+
+  scrub.max = player.duration;
+
+  const format = (t) => Math.floor(t / 60) + ":" + (t % 60).toFixed(1).padStart(4, "0");
+
+  // The last frame starting at or before `t`.
+  function frameAt(t) {
+    let lo = 0;
+    let hi = frames.length - 1;
+
+    while (lo < hi) {
+      const mid = (lo + hi + 1) >> 1;
+      if (frames[mid].time <= t) lo = mid; else hi = mid - 1;
+    }
+
+    return lo;
+  }
+
+  // Frames only hold the area that changed, so drawing frame `index` starts
+  // from the nearest key frame, or after the frame last drawn when closer.
+  function draw(index) {
+    let start = index;
+    while (!frames[start].key) start--;
+    if (index > drawn && drawn >= start) start = drawn + 1;
+
+    for (let i = start; i <= index; i++) {
+      if (images[i]) ctx.drawImage(images[i], frames[i].x, frames[i].y);
+    }
+
+    drawn = index;
+  }
+
+  function seek(t) {
+    time = Math.min(Math.max(t, 0), player.duration);
+    const index = frameAt(time);
+    if (index !== drawn) draw(index);
+    scrub.value = time;
+    clock.textContent = format(time) + " / " + format(player.duration);
+
+    chapterButtons.forEach((button, i) => {
+      const next = player.chapters[i + 1];
+      button.classList.toggle("current", player.chapters[i].time <= time && (!next || time < next.time));
+    });
+  }
+
+  function tick(now) {
+    if (!playing) return;
+    let t = (now - origin) / 1000;
+
+    if (t >= player.duration) {
+      plays += Math.floor(t / player.duration);
+
+      if (player.loop_count !== null && plays >= player.loop_count) {
+        seek(player.duration);
+        pause();
+        return;
+      }
+
+      t %= player.duration;
+      origin = now - t * 1000;
+    }
+
+    seek(t);
+    requestAnimationFrame(tick);
+  }
+
+  function play() {
+    if (time >= player.duration) {
+      time = 0;
+      plays = 0;
+    }
+
+    playing = true;
+    origin = performance.now() - time * 1000;
+    toggle.textContent = "Pause";
+    requestAnimationFrame(tick);
+  }
+
+  function pause() {
+    playing = false;
+    toggle.textContent = "Play";
+  }
+
+  function jump(t) {
+    seek(t);
+    origin = performance.now() - time * 1000;
+  }
+
+  toggle.addEventListener("click", () => (playing ? pause() : play()));
+  scrub.addEventListener("input", () => jump(Number(scrub.value)));
+
+  const list = document.getElementById("agg-chapters");
+
+  for (const chapter of player.chapters) {
+    const item = document.createElement("li");
+    const button = document.createElement("button");
+    button.type = "button";
+    button.textContent = format(chapter.time) + " " + chapter.label;
+    button.addEventListener("click", () => jump(chapter.time));
+    item.append(button);
+    list.append(item);
+    chapterButtons.push(button);
+  }
+
+  Promise.all(images.filter(Boolean).map((image) => image.decode())).then(() => {
+    seek(0);
+    play();
+  });
+})();
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::settings;
+
+    fn image(value: u8) -> ImgVec<RGBA8> {
+        ImgVec::new(vec![RGBA8::new(value, value, value, 255); 4], 2, 2)
+    }
+
+    #[test]
+    fn stores_changes_and_escapes_labels() {
+        let settings = Settings {
+            frame_count: 3,
+            ..settings(2, 2)
+        };
+
+        let chapters = vec![Cue {
+            start: 0.0,
+            end: 3.0,
+            text: "</script>".to_owned(),
+        }];
+
+        let frames = vec![(image(0), 0.0), (image(0), 1.0), (image(9), 2.0)];
+        let mut output = Vec::new();
+
+        Box::new(HtmlEncoder::new(settings, chapters))
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        let html = String::from_utf8(output).unwrap();
+
+        assert_eq!(html.matches("data:image/png;base64,").count(), 2);
+        assert!(html.contains(r#""time":1.0,"x":0,"y":0,"key":false,"src":null"#));
+        assert!(html.contains(r#""duration":3.0"#));
+        assert!(html.contains(r#""label":"<\/script>""#));
+        assert!(!html.contains("http"));
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use imgref::ImgVec;
+use rgb::RGBA8;
+use serde::Serialize;
+
+use super::{full_rect, rgb_bytes, Encoder, Rect, Settings};
+
+/// Writes a single frame as a PNG image, or the frame at `index` of any number
+/// of frames.
+pub struct PngEncoder {
+    index: Option<usize>,
+}
+
+/// Writes every frame as a numbered PNG file into a directory, and a JSON
+/// manifest of the frame timings to the output.
+pub struct PngSequenceEncoder {
+    settings: Settings,
+    dir: PathBuf,
+}
+
+#[derive(Serialize)]
+struct Manifest {
+    width: usize,
+    height: usize,
+    frames: Vec<ManifestFrame>,
+}
+
+#[derive(Serialize)]
+struct ManifestFrame {
+    file: String,
+    time: f64,
+    duration: f64,
+}
+
+impl PngEncoder {
+    pub fn new(index: Option<usize>) -> Self {
+        Self { index }
+    }
+}
+
+impl Encoder for PngEncoder {
+    fn encode(
+        self: Box<Self>,
+        frames: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()> {
+        if let Some(index) = self.index {
+            let Some((image, _)) = frames.nth(index) else {
+                bail!("no frame {} to write", index + 1);
+            };
+
+            return write_image(&image, output);
+        }
+
+        let Some((image, _)) = frames.next() else {
+            bail!("no frame to write");
+        };
+
+        if frames.next().is_some() {
+            bail!("a PNG image holds a single frame");
+        }
+
+        write_image(&image, output)
+    }
+}
+
+impl PngSequenceEncoder {
+    pub fn new(settings: Settings, dir: PathBuf) -> Self {
+        Self { settings, dir }
+    }
+}
+
+impl Encoder for PngSequenceEncoder {
+    fn encode(
+        self: Box<Self>,
+        frames: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()> {
+        let settings = &self.settings;
+        let mut progress_bar = super::progress_bar(settings);
+        let digits = settings.frame_count.to_string().len().max(4);
+        let mut manifest_frames: Vec<ManifestFrame> = Vec::new();
+
+        for (i, (image, time)) in frames.enumerate() {
+            if let Some(pb) = progress_bar.as_mut() {
+                pb.inc();
+            }
+
+            let file = format!("frame-{:0digits$}.png", i + 1);
+            let mut writer = BufWriter::new(File::create(self.dir.join(&file))?);
+            write_image(&image, &mut writer)?;
+            writer.flush()?;
+
+            if let Some(prev) = manifest_frames.last_mut() {
+                prev.duration = time - prev.time;
+            }
+
+            manifest_frames.push(ManifestFrame {
+                file,
+                time,
+                duration: settings.last_frame_duration,
+            });
+        }
+
+        let manifest = Manifest {
+            width: settings.width,
+            height: settings.height,
+            frames: manifest_frames,
+        };
+
+        serde_json::to_writer_pretty(&mut *output, &manifest)?;
+        writeln!(output)?;
+
+        super::finish_progress_bar(progress_bar);
+
+        Ok(())
+    }
+}
+
+pub(super) fn write_image(image: &ImgVec<RGBA8>, output: &mut dyn Write) -> Result<()> {
+    write_rect(image, full_rect(image), output)
+}
+
+/// Write the `rect` area of `image` as a PNG image.
+pub(super) fn write_rect(image: &ImgVec<RGBA8>, rect: Rect, output: &mut dyn Write) -> Result<()> {
+    let mut encoder = png::Encoder::new(output, rect.width as u32, rect.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Best);
+    encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgb_bytes(image, rect))?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{image, settings};
+
+    #[test]
+    fn writes_numbered_frames_and_manifest() {
+        let dir = std::env::temp_dir().join(format!("agg-png-sequence-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let settings = Settings {
+            frame_count: 2,
+            last_frame_duration: 3.0,
+            ..settings(2, 1)
+        };
+
+        let frames = vec![(image(&[0, 0], 2), 0.0), (image(&[0, 9], 2), 1.5)];
+        let mut output = Vec::new();
+
+        Box::new(PngSequenceEncoder::new(settings, dir.clone()))
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        let manifest: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(
+            manifest,
+            serde_json::json!({
+                "width": 2,
+                "height": 1,
+                "frames": [
+                    { "file": "frame-0001.png", "time": 0.0, "duration": 1.5 },
+                    { "file": "frame-0002.png", "time": 1.5, "duration": 3.0 },
+                ],
+            })
+        );
+
+        let decoder = png::Decoder::new(File::open(dir.join("frame-0002.png")).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+
+        assert_eq!(buf, [0, 0, 0, 9, 9, 9]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn single_image_rejects_multiple_frames() {
+        let frames = vec![(image(&[0], 1), 0.0), (image(&[1], 1), 1.0)];
+        let mut output = Vec::new();
+
+        let result = Box::new(PngEncoder::new(None)).encode(&mut frames.into_iter(), &mut output);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn still_writes_the_frame_at_its_index() {
+        let frames = vec![
+            (image(&[0], 1), 0.0),
+            (image(&[7], 1), 1.0),
+            (image(&[0], 1), 2.0),
+        ];
+        let mut output = Vec::new();
+
+        Box::new(PngEncoder::new(Some(1)))
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        let decoder = png::Decoder::new(output.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+
+        assert_eq!(buf, [7, 7, 7]);
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::Settings;
+use crate::renderer::ResvgRenderer;
+use crate::terminal::Snapshot;
+
+/// Writes an animated SVG: every frame is a group in a vertical film strip,
+/// which a CSS animation shifts into view at the frame's start time. Text runs,
+/// background rects and whole rows are defined once and referenced from every
+/// frame that shows them.
+pub struct SvgEncoder<'a> {
+    settings: Settings,
+    renderer: ResvgRenderer<'a>,
+}
+
+/// Shared `<defs>` content, keyed by markup.
+#[derive(Default)]
+struct Defs {
+    ids: HashMap<String, usize>,
+    svg: String,
+}
+
+impl Defs {
+    /// Id of the definition with the given markup, added on first use.
+    fn intern(&mut self, markup: String) -> usize {
+        let next_id = self.ids.len();
+
+        *self.ids.entry(markup).or_insert_with_key(|markup| {
+            write!(self.svg, r#"<g id="d{next_id}">{markup}</g>"#).unwrap();
+
+            next_id
+        })
+    }
+
+    /// Id of a group placing each of `elements` at its horizontal offset, or
+    /// `None` when there are no elements.
+    fn intern_group(&mut self, elements: Vec<(f64, String)>) -> Option<usize> {
+        if elements.is_empty() {
+            return None;
+        }
+
+        let mut markup = String::new();
+
+        for (x, element) in elements {
+            let id = self.intern(element);
+            write!(markup, r##"<use href="#d{id}" x="{x:.3}%" />"##).unwrap();
+        }
+
+        Some(self.intern(markup))
+    }
+}
+
+impl<'a> SvgEncoder<'a> {
+    pub fn new(settings: Settings, renderer: ResvgRenderer<'a>) -> Self {
+        Self { settings, renderer }
+    }
+
+    /// Encode `frames`, each a terminal snapshot paired with its start time in
+    /// seconds, into `output`.
+    pub fn encode(
+        self,
+        frames: &mut dyn Iterator<Item = (Snapshot, f64)>,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        let settings = &self.settings;
+        let mut progress_bar = super::progress_bar(settings);
+
+        let mut defs = Defs::default();
+        let mut strip = String::new();
+        let mut times = Vec::new();
+
+        for (i, (snapshot, time)) in frames.enumerate() {
+            if let Some(pb) = progress_bar.as_mut() {
+                pb.inc();
+            }
+
+            // All backgrounds go below all text, as in rasterized frames.
+            let mut backgrounds = String::new();
+            let mut text = String::new();
+
+            for row in 0..snapshot.lines.len() {
+                let y = self.renderer.svg_row_offset(row);
+                let elements = self.renderer.svg_for_row(&snapshot, row);
+
+                if let Some(id) = defs.intern_group(elements.backgrounds) {
+                    write!(backgrounds, r##"<use href="#d{id}" y="{y:.3}%" />"##)?;
+                }
+
+                if let Some(id) = defs.intern_group(elements.text) {
+                    write!(text, r##"<use href="#d{id}" y="{y:.3}%" />"##)?;
+                }
+            }
+
+            write!(
+                strip,
+                r#"<g transform="translate(0 {})">{backgrounds}{text}</g>"#,
+                i * settings.height
+            )?;
+
+            times.push(time);
+        }
+
+        output.write_all(self.renderer.svg_header().as_bytes())?;
+        output.write_all(self.animation_style(&times).as_bytes())?;
+        write!(output, "<defs>{}</defs>", defs.svg)?;
+        write!(output, r#"<g class="frames">{strip}</g>"#)?;
+        output.write_all(self.renderer.svg_footer().as_bytes())?;
+
+        super::finish_progress_bar(progress_bar);
+
+        Ok(())
+    }
+
+    /// CSS keyframes moving the film strip by one frame height at each frame
+    /// start time. A single frame needs no animation.
+    fn animation_style(&self, times: &[f64]) -> String {
+        let settings = &self.settings;
+
+        let Some(last_time) = times.last() else {
+            return String::new();
+        };
+
+        let duration = last_time + settings.last_frame_duration;
+
+        if times.len() < 2 || duration <= 0.0 {
+            return String::new();
+        }
+
+        let mut style = String::from("<style>\n@keyframes frames {\n");
+
+        for (i, time) in times.iter().enumerate() {
+            let offset = i * settings.height;
+            let pct = 100.0 * time / duration;
+
+            writeln!(style, "{pct:.4}% {{ transform: translateY(-{offset}px) }}").unwrap();
+        }
+
+        // The last frame stays in place when a non-looping animation ends.
+        let offset = (times.len() - 1) * settings.height;
+        writeln!(style, "100% {{ transform: translateY(-{offset}px) }}").unwrap();
+        style.push_str("}\n");
+
+        let iterations = match settings.loop_count {
+            Some(plays) => format!("{plays} forwards"),
+            None => String::from("infinite"),
+        };
+
+        writeln!(
+            style,
+            ".frames {{ animation: frames {duration:.3}s steps(1, end) {iterations} }}"
+        )
+        .unwrap();
+
+        style.push_str("</style>");
+
+        style
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::settings;
+    use crate::renderer;
+
+    fn snapshot(vt: &avt::Vt) -> Snapshot {
+        Snapshot::from_vt(vt)
+    }
+
+    fn encoder(settings: Settings) -> SvgEncoder<'static> {
+        let renderer = renderer::resvg(renderer::Settings {
+            terminal_size: (4, 2),
+            font_db: fontdb::Database::new(),
+            font_families: vec!["JetBrains Mono".to_owned()],
+            text_family: "JetBrains Mono".to_owned(),
+            font_aa_levels: crate::DEFAULT_FONT_AA_LEVELS,
+            font_size: 16,
+            line_height: 1.4,
+            theme: "000000,ffffff,000000,111111,222222,333333,444444,555555,666666,777777"
+                .parse()
+                .unwrap(),
+            bold_is_bright: false,
+            hinting: true,
+            cursor_color: None,
+            blink_as_bold: false,
+        });
+
+        SvgEncoder::new(settings, renderer)
+    }
+
+    #[test]
+    fn shares_rows_between_frames() {
+        let settings = Settings {
+            frame_count: 2,
+            loop_count: Some(1),
+            ..settings(100, 50)
+        };
+
+        let mut vt = avt::Vt::new(4, 2);
+        vt.feed_str("ab\r\n");
+        let first = snapshot(&vt);
+        vt.feed_str("cd");
+        let second = snapshot(&vt);
+
+        let mut frames = vec![(first, 0.0), (second, 1.0)].into_iter();
+        let mut output = Vec::new();
+        encoder(settings).encode(&mut frames, &mut output).unwrap();
+        let svg = String::from_utf8(output).unwrap();
+
+        // Text runs "ab" and "cd", a cursor rect placed at two offsets, and a
+        // group for each distinct row layer. The "ab" row is shared by both
+        // frames.
+        assert_eq!(svg.matches(r#"<g id="d"#).count(), 7);
+        assert_eq!(svg.matches("<use ").count(), 9);
+
+        assert!(svg.contains("0.0000% { transform: translateY(-0px) }"));
+        assert!(svg.contains("50.0000% { transform: translateY(-50px) }"));
+        assert!(svg.contains("100% { transform: translateY(-50px) }"));
+        assert!(svg.contains("animation: frames 2.000s steps(1, end) 1 forwards"));
+
+        usvg::Tree::from_str(&svg, &usvg::Options::default()).unwrap();
+    }
+
+    #[test]
+    fn keeps_wide_characters_next_to_their_neighbors() {
+        let settings = Settings {
+            loop_count: Some(1),
+            ..settings(100, 50)
+        };
+
+        let mut vt = avt::Vt::new(4, 2);
+        vt.feed_str("日 b");
+
+        let mut frames = vec![(snapshot(&vt), 0.0)].into_iter();
+        let mut output = Vec::new();
+        encoder(settings).encode(&mut frames, &mut output).unwrap();
+        let svg = String::from_utf8(output).unwrap();
+
+        assert!(svg.contains(">日 b</text>"));
+        assert_eq!(svg.matches(r#"x="0.000% 33.333% 50.000%""#).count(), 1);
+    }
+}
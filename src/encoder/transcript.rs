@@ -0,0 +1,210 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::terminal::Snapshot;
+
+/// Writes the text of every frame under its start time, either plain or with
+/// text attributes re-encoded as ANSI SGR sequences. A frame whose text is the
+/// same as the previous one's, e.g. when only the cursor moved, is left out.
+pub struct TranscriptEncoder {
+    ansi: bool,
+}
+
+impl TranscriptEncoder {
+    pub fn new(ansi: bool) -> Self {
+        Self { ansi }
+    }
+
+    /// Encode `frames`, each a terminal snapshot paired with its start time in
+    /// seconds, into `output`.
+    pub fn encode(
+        self,
+        frames: &mut dyn Iterator<Item = (Snapshot, f64)>,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        let mut prev_text: Option<String> = None;
+
+        for (snapshot, time) in frames {
+            let text = self.text(&snapshot);
+
+            if prev_text.as_ref() == Some(&text) {
+                continue;
+            }
+
+            if prev_text.is_some() {
+                writeln!(output)?;
+            }
+
+            writeln!(output, "[{time:.3}s]")?;
+            output.write_all(text.as_bytes())?;
+            prev_text = Some(text);
+        }
+
+        Ok(())
+    }
+
+    /// Snapshot lines without trailing blanks, and without trailing blank lines.
+    fn text(&self, snapshot: &Snapshot) -> String {
+        let mut lines: Vec<String> = (0..snapshot.lines.len())
+            .map(|row| {
+                if self.ansi {
+                    ansi_line(snapshot, row)
+                } else {
+                    snapshot.text(row).trim_end().to_owned()
+                }
+            })
+            .collect();
+
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+}
+
+/// The text of row `row` with SGR sequences wherever the pen changes, ending
+/// with the default pen.
+fn ansi_line(snapshot: &Snapshot, row: usize) -> String {
+    let mut runs: Vec<(avt::Pen, String)> = Vec::new();
+
+    for (_, cell, text) in snapshot.cells(row) {
+        match runs.last_mut() {
+            Some((pen, run)) if pen == cell.pen() => run.push_str(&text),
+            _ => runs.push((*cell.pen(), text)),
+        }
+    }
+
+    // Spaces in the default pen at the end of the line are invisible.
+    while let Some((pen, text)) = runs.last_mut() {
+        if !pen.is_default() {
+            break;
+        }
+
+        let trimmed_len = text.trim_end_matches(' ').len();
+        text.truncate(trimmed_len);
+
+        if !text.is_empty() {
+            break;
+        }
+
+        runs.pop();
+    }
+
+    let mut out = String::new();
+    let mut current = avt::Pen::default();
+
+    for (pen, text) in runs {
+        if pen != current {
+            out.push_str(&sgr(&pen));
+            current = pen;
+        }
+
+        out.push_str(&text);
+    }
+
+    if !current.is_default() {
+        out.push_str("\x1b[0m");
+    }
+
+    out
+}
+
+/// A sequence resetting all attributes and then setting those of `pen`.
+fn sgr(pen: &avt::Pen) -> String {
+    let mut params = String::from("0");
+
+    for (set, param) in [
+        (pen.is_bold(), 1),
+        (pen.is_faint(), 2),
+        (pen.is_italic(), 3),
+        (pen.is_underline(), 4),
+        (pen.is_blink(), 5),
+        (pen.is_inverse(), 7),
+        (pen.is_strikethrough(), 9),
+    ] {
+        if set {
+            write!(params, ";{param}").unwrap();
+        }
+    }
+
+    if let Some(color) = pen.foreground() {
+        write!(params, ";{}", color_params(color, 30, 90, 38)).unwrap();
+    }
+
+    if let Some(color) = pen.background() {
+        write!(params, ";{}", color_params(color, 40, 100, 48)).unwrap();
+    }
+
+    format!("\x1b[{params}m")
+}
+
+fn color_params(color: avt::Color, base: u8, bright_base: u8, extended: u8) -> String {
+    match color {
+        avt::Color::Indexed(n) if n < 8 => (base + n).to_string(),
+        avt::Color::Indexed(n) if n < 16 => (bright_base + n - 8).to_string(),
+        avt::Color::Indexed(n) => format!("{extended};5;{n}"),
+        avt::Color::RGB(c) => format!("{extended};2;{};{};{}", c.r, c.g, c.b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(ansi: bool, frames: Vec<(Snapshot, f64)>) -> String {
+        let mut output = Vec::new();
+
+        TranscriptEncoder::new(ansi)
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn plain_text_skips_frames_with_unchanged_text() {
+        let mut vt = avt::Vt::new(10, 3);
+        vt.feed_str("\x1b[1mab\x1b[0m  \r\n");
+        let first = Snapshot::from_vt(&vt);
+        vt.feed_str("  ");
+        let cursor_moved = Snapshot::from_vt(&vt);
+        vt.feed_str("c");
+        let second = Snapshot::from_vt(&vt);
+
+        let text = encode(
+            false,
+            vec![(first, 0.0), (cursor_moved, 0.5), (second, 1.25)],
+        );
+
+        assert_eq!(text, "[0.000s]\nab\n\n[1.250s]\nab\n  c\n");
+    }
+
+    #[test]
+    fn keeps_grapheme_clusters_whole() {
+        let mut terminal = crate::terminal::build((10, 1));
+        crate::terminal::feed_str(&mut terminal, "e\u{301}\x1b[1m👍🏽\x1b[0mx");
+        let frames = || vec![(Snapshot::new(&terminal), 0.0)];
+
+        assert_eq!(encode(false, frames()), "[0.000s]\ne\u{301}👍🏽x\n");
+        assert_eq!(
+            encode(true, frames()),
+            "[0.000s]\ne\u{301}\x1b[0;1m👍🏽\x1b[0mx\n"
+        );
+    }
+
+    #[test]
+    fn ansi_reencodes_attributes_and_colors() {
+        let mut vt = avt::Vt::new(20, 1);
+        vt.feed_str("a\x1b[1;31mb\x1b[38;5;200;44mc\x1b[0m d\x1b[48;2;1;2;3m  ");
+
+        let text = encode(true, vec![(Snapshot::from_vt(&vt), 0.0)]);
+
+        assert_eq!(
+            text,
+            "[0.000s]\na\x1b[0;1;31mb\x1b[0;1;38;5;200;44mc\x1b[0m d\x1b[0;48;2;1;2;3m  \x1b[0m\n"
+        );
+    }
+}
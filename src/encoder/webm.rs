@@ -0,0 +1,480 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use imgref::ImgVec;
+use rav1e::color::{
+    ColorDescription, ColorPrimaries, MatrixCoefficients, PixelRange, TransferCharacteristics,
+};
+use rav1e::config::{Config, EncoderConfig};
+use rav1e::data::{EncoderStatus, Frame, FrameType, Rational};
+use rav1e::Context;
+use rgb::RGBA8;
+
+use super::{Encoder, Settings};
+
+/// rav1e speed preset, 0 (slowest) to 10 (fastest). Terminal frames are mostly
+/// flat and static, so the fastest presets still compress them well.
+const SPEED_PRESET: u8 = 10;
+
+/// Longest stretch between key frames, in seconds, bounding seek time.
+const MAX_KEY_FRAME_INTERVAL: u64 = 10;
+
+/// Longest cluster, in milliseconds. Block timestamps are 16-bit offsets from
+/// the cluster timestamp.
+const MAX_CLUSTER_DURATION: u64 = 30_000;
+
+const EBML: u32 = 0x1A45DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x18538067;
+const SEEK_HEAD: u32 = 0x114D9B74;
+const SEEK: u32 = 0x4DBB;
+const SEEK_ID: u32 = 0x53AB;
+const SEEK_POSITION: u32 = 0x53AC;
+const INFO: u32 = 0x1549A966;
+const TIMESTAMP_SCALE: u32 = 0x2AD7B1;
+const DURATION: u32 = 0x4489;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const FLAG_LACING: u32 = 0x9C;
+const DEFAULT_DURATION: u32 = 0x23E383;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63A2;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const CLUSTER: u32 = 0x1F43B675;
+const TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+const CUES: u32 = 0x1C53BB6B;
+const CUE_POINT: u32 = 0xBB;
+const CUE_TIME: u32 = 0xB3;
+const CUE_TRACK_POSITIONS: u32 = 0xB7;
+const CUE_TRACK: u32 = 0xF7;
+const CUE_CLUSTER_POSITION: u32 = 0xF1;
+
+/// Writes AV1 video in a WebM container, at a constant frame rate.
+pub struct WebmEncoder {
+    settings: Settings,
+    fps: u8,
+}
+
+/// An encoded video frame.
+struct Block {
+    timestamp: u64,
+    key_frame: bool,
+    data: Vec<u8>,
+}
+
+impl WebmEncoder {
+    pub fn new(settings: Settings, fps: u8) -> Self {
+        Self { settings, fps }
+    }
+}
+
+impl Encoder for WebmEncoder {
+    fn encode(
+        self: Box<Self>,
+        frames: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()> {
+        let settings = &self.settings;
+        let fps = self.fps as u64;
+        let mut progress_bar = super::progress_bar(settings);
+
+        let encoder_config = EncoderConfig {
+            width: settings.width,
+            height: settings.height,
+            time_base: Rational::new(1, fps),
+            pixel_range: PixelRange::Limited,
+            color_description: Some(ColorDescription {
+                color_primaries: ColorPrimaries::BT709,
+                transfer_characteristics: TransferCharacteristics::BT709,
+                matrix_coefficients: MatrixCoefficients::BT709,
+            }),
+            max_key_frame_interval: fps * MAX_KEY_FRAME_INTERVAL,
+            ..EncoderConfig::with_speed_preset(SPEED_PRESET)
+        };
+
+        let mut context: Context<u8> = Config::new()
+            .with_encoder_config(encoder_config)
+            .new_context()
+            .map_err(|e| anyhow!("invalid video encoder configuration: {e}"))?;
+
+        let mut blocks = Vec::new();
+
+        // Video frame n is shown at n / fps and repeats the latest frame that
+        // started by then.
+        let mut held: Option<(Arc<Frame<u8>>, f64)> = None;
+        let mut frame_count = 0;
+
+        for (image, time) in frames {
+            if let Some(pb) = progress_bar.as_mut() {
+                pb.inc();
+            }
+
+            if let Some((frame, _)) = &held {
+                while (frame_count as f64) < (time * fps as f64).round() {
+                    send_frame(&mut context, frame, &mut blocks, fps)?;
+                    frame_count += 1;
+                }
+            }
+
+            held = Some((Arc::new(yuv_frame(&context, &image)), time));
+        }
+
+        if let Some((frame, time)) = held {
+            let end = ((time + settings.last_frame_duration) * fps as f64).round() as u64;
+
+            loop {
+                send_frame(&mut context, &frame, &mut blocks, fps)?;
+                frame_count += 1;
+
+                if frame_count >= end {
+                    break;
+                }
+            }
+        }
+
+        context.flush();
+        receive_packets(&mut context, &mut blocks, fps)?;
+
+        let duration = (frame_count * 1000) as f64 / fps as f64;
+        let codec_private = context.container_sequence_header();
+
+        output.write_all(&ebml_header())?;
+        output.write_all(&segment(settings, fps, duration, &codec_private, &blocks))?;
+
+        super::finish_progress_bar(progress_bar);
+
+        Ok(())
+    }
+}
+
+fn send_frame(
+    context: &mut Context<u8>,
+    frame: &Arc<Frame<u8>>,
+    blocks: &mut Vec<Block>,
+    fps: u64,
+) -> Result<()> {
+    loop {
+        match context.send_frame(Arc::clone(frame)) {
+            Ok(()) => break,
+            Err(EncoderStatus::EnoughData) => receive_packets(context, blocks, fps)?,
+            Err(e) => return Err(anyhow!("video encoding failed: {e}")),
+        }
+    }
+
+    receive_packets(context, blocks, fps)
+}
+
+/// Collect the packets the encoder has ready, until it needs more frames or,
+/// after a flush, has no more packets.
+fn receive_packets(context: &mut Context<u8>, blocks: &mut Vec<Block>, fps: u64) -> Result<()> {
+    loop {
+        match context.receive_packet() {
+            Ok(packet) => blocks.push(Block {
+                timestamp: packet.input_frameno * 1000 / fps,
+                key_frame: packet.frame_type == FrameType::KEY,
+                data: packet.data,
+            }),
+
+            Err(EncoderStatus::Encoded) => {}
+            Err(EncoderStatus::NeedMoreData | EncoderStatus::LimitReached) => return Ok(()),
+            Err(e) => return Err(anyhow!("video encoding failed: {e}")),
+        }
+    }
+}
+
+/// Convert an RGB image to 4:2:0 YUV in limited-range BT.709.
+fn yuv_frame(context: &Context<u8>, image: &ImgVec<RGBA8>) -> Frame<u8> {
+    let (width, height) = (image.width(), image.height());
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut y = Vec::with_capacity(width * height);
+    let mut u = Vec::with_capacity(chroma_width * chroma_height);
+    let mut v = Vec::with_capacity(chroma_width * chroma_height);
+
+    for pixel in image.pixels() {
+        let (r, g, b) = (pixel.r as f32, pixel.g as f32, pixel.b as f32);
+        let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        y.push((16.0 + luma * 219.0 / 255.0).round() as u8);
+    }
+
+    for cy in 0..chroma_height {
+        for cx in 0..chroma_width {
+            let mut sum = [0.0; 3];
+            let mut count = 0.0;
+
+            for py in (cy * 2)..(cy * 2 + 2).min(height) {
+                for px in (cx * 2)..(cx * 2 + 2).min(width) {
+                    let pixel = image[(px, py)];
+                    sum[0] += pixel.r as f32;
+                    sum[1] += pixel.g as f32;
+                    sum[2] += pixel.b as f32;
+                    count += 1.0;
+                }
+            }
+
+            let [r, g, b] = sum.map(|c| c / count);
+            let cb = -0.1146 * r - 0.3854 * g + 0.5 * b;
+            let cr = 0.5 * r - 0.4542 * g - 0.0458 * b;
+            u.push((128.0 + cb * 224.0 / 255.0).round() as u8);
+            v.push((128.0 + cr * 224.0 / 255.0).round() as u8);
+        }
+    }
+
+    let mut frame = context.new_frame();
+    frame.planes[0].copy_from_raw_u8(&y, width, 1);
+    frame.planes[1].copy_from_raw_u8(&u, chroma_width, 1);
+    frame.planes[2].copy_from_raw_u8(&v, chroma_width, 1);
+
+    for plane in &mut frame.planes {
+        plane.pad(width, height);
+    }
+
+    frame
+}
+
+fn ebml_header() -> Vec<u8> {
+    let mut payload = Vec::new();
+    uint_element(&mut payload, EBML_VERSION, 1);
+    uint_element(&mut payload, EBML_READ_VERSION, 1);
+    uint_element(&mut payload, EBML_MAX_ID_LENGTH, 4);
+    uint_element(&mut payload, EBML_MAX_SIZE_LENGTH, 8);
+    element(&mut payload, DOC_TYPE, b"webm");
+    uint_element(&mut payload, DOC_TYPE_VERSION, 4);
+    uint_element(&mut payload, DOC_TYPE_READ_VERSION, 2);
+
+    let mut header = Vec::new();
+    element(&mut header, EBML, &payload);
+
+    header
+}
+
+/// The complete segment: a seek head, segment info, the track, clusters of
+/// blocks, and cues pointing at the clusters that start with a key frame.
+fn segment(
+    settings: &Settings,
+    fps: u64,
+    duration: f64,
+    codec_private: &[u8],
+    blocks: &[Block],
+) -> Vec<u8> {
+    let mut info = Vec::new();
+    let mut payload = Vec::new();
+    uint_element(&mut payload, TIMESTAMP_SCALE, 1_000_000);
+    float_element(&mut payload, DURATION, duration);
+    element(&mut payload, MUXING_APP, b"agg");
+    element(&mut payload, WRITING_APP, b"agg");
+    element(&mut info, INFO, &payload);
+
+    let mut tracks = Vec::new();
+    let mut video = Vec::new();
+    uint_element(&mut video, PIXEL_WIDTH, settings.width as u64);
+    uint_element(&mut video, PIXEL_HEIGHT, settings.height as u64);
+    let mut entry = Vec::new();
+    uint_element(&mut entry, TRACK_NUMBER, 1);
+    uint_element(&mut entry, TRACK_UID, 1);
+    uint_element(&mut entry, TRACK_TYPE, 1);
+    uint_element(&mut entry, FLAG_LACING, 0);
+    uint_element(&mut entry, DEFAULT_DURATION, 1_000_000_000 / fps);
+    element(&mut entry, CODEC_ID, b"V_AV1");
+    element(&mut entry, CODEC_PRIVATE, codec_private);
+    element(&mut entry, VIDEO, &video);
+    let mut payload = Vec::new();
+    element(&mut payload, TRACK_ENTRY, &entry);
+    element(&mut tracks, TRACKS, &payload);
+
+    let mut clusters = Vec::new();
+    let mut cue_points = Vec::new();
+    let mut cluster: Option<(u64, Vec<u8>)> = None;
+
+    for block in blocks {
+        let starts_cluster = match &cluster {
+            Some((timestamp, _)) => {
+                block.key_frame || block.timestamp - timestamp >= MAX_CLUSTER_DURATION
+            }
+
+            None => true,
+        };
+
+        if starts_cluster {
+            if let Some((_, payload)) = cluster.take() {
+                element(&mut clusters, CLUSTER, &payload);
+            }
+
+            if block.key_frame {
+                cue_points.push((block.timestamp, clusters.len()));
+            }
+
+            let mut payload = Vec::new();
+            uint_element(&mut payload, TIMESTAMP, block.timestamp);
+            cluster = Some((block.timestamp, payload));
+        }
+
+        let (timestamp, payload) = cluster.as_mut().unwrap();
+        let mut simple_block = vec![0x81];
+        let offset = (block.timestamp - *timestamp) as i16;
+        simple_block.extend_from_slice(&offset.to_be_bytes());
+        simple_block.push(if block.key_frame { 0x80 } else { 0 });
+        simple_block.extend_from_slice(&block.data);
+        element(payload, SIMPLE_BLOCK, &simple_block);
+    }
+
+    if let Some((_, payload)) = cluster {
+        element(&mut clusters, CLUSTER, &payload);
+    }
+
+    // Segment positions are only known once the seek head size is. Seek
+    // positions have a fixed width, so the size doesn't depend on them.
+    let seek_head_len = seek_head(&[(INFO, 0), (TRACKS, 0), (CUES, 0)]).len();
+    let info_position = seek_head_len;
+    let tracks_position = info_position + info.len();
+    let clusters_position = tracks_position + tracks.len();
+    let cues_position = clusters_position + clusters.len();
+
+    let mut cues = Vec::new();
+    let mut payload = Vec::new();
+
+    for (timestamp, offset) in cue_points {
+        let mut positions = Vec::new();
+        uint_element(&mut positions, CUE_TRACK, 1);
+        uint_element(
+            &mut positions,
+            CUE_CLUSTER_POSITION,
+            (clusters_position + offset) as u64,
+        );
+
+        let mut point = Vec::new();
+        uint_element(&mut point, CUE_TIME, timestamp);
+        element(&mut point, CUE_TRACK_POSITIONS, &positions);
+        element(&mut payload, CUE_POINT, &point);
+    }
+
+    element(&mut cues, CUES, &payload);
+
+    let mut payload = seek_head(&[
+        (INFO, info_position),
+        (TRACKS, tracks_position),
+        (CUES, cues_position),
+    ]);
+
+    payload.extend_from_slice(&info);
+    payload.extend_from_slice(&tracks);
+    payload.extend_from_slice(&clusters);
+    payload.extend_from_slice(&cues);
+
+    let mut segment = Vec::new();
+    element(&mut segment, SEGMENT, &payload);
+
+    segment
+}
+
+fn seek_head(entries: &[(u32, usize)]) -> Vec<u8> {
+    let mut payload = Vec::new();
+
+    for (id, position) in entries {
+        let mut seek = Vec::new();
+        element(&mut seek, SEEK_ID, &id_bytes(*id));
+        element(&mut seek, SEEK_POSITION, &(*position as u64).to_be_bytes());
+        element(&mut payload, SEEK, &seek);
+    }
+
+    let mut seek_head = Vec::new();
+    element(&mut seek_head, SEEK_HEAD, &payload);
+
+    seek_head
+}
+
+fn id_bytes(id: u32) -> Vec<u8> {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count();
+
+    bytes[skip..].to_vec()
+}
+
+/// Append an EBML element with a variable-length size in as few bytes as fit.
+fn element(buf: &mut Vec<u8>, id: u32, payload: &[u8]) {
+    buf.extend_from_slice(&id_bytes(id));
+
+    let size = payload.len() as u64;
+    let len = (1..8).find(|n| size < (1 << (7 * n)) - 1).unwrap_or(8);
+    let marked = size | (1 << (7 * len));
+    buf.extend_from_slice(&marked.to_be_bytes()[8 - len..]);
+
+    buf.extend_from_slice(payload);
+}
+
+fn uint_element(buf: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count().min(7);
+
+    element(buf, id, &bytes[skip..]);
+}
+
+fn float_element(buf: &mut Vec<u8>, id: u32, value: f64) {
+    element(buf, id, &value.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::settings;
+
+    #[test]
+    fn element_sizes_use_the_shortest_encoding() {
+        let mut buf = Vec::new();
+        element(&mut buf, SIMPLE_BLOCK, &[7; 3]);
+        assert_eq!(buf, [0xA3, 0x83, 7, 7, 7]);
+
+        let mut buf = Vec::new();
+        element(&mut buf, CLUSTER, &[0; 200]);
+        assert_eq!(&buf[..6], [0x1F, 0x43, 0xB6, 0x75, 0x40, 200]);
+
+        let mut buf = Vec::new();
+        uint_element(&mut buf, TIMESTAMP, 0);
+        assert_eq!(buf, [0xE7, 0x81, 0]);
+    }
+
+    #[test]
+    fn holds_frames_at_a_constant_frame_rate() {
+        let settings = Settings {
+            frame_count: 2,
+            last_frame_duration: 0.5,
+            ..settings(16, 16)
+        };
+
+        let black = ImgVec::new(vec![RGBA8::new(0, 0, 0, 255); 256], 16, 16);
+        let white = ImgVec::new(vec![RGBA8::new(255, 255, 255, 255); 256], 16, 16);
+        let frames = vec![(black, 0.0), (white, 1.0)];
+        let mut output = Vec::new();
+
+        Box::new(WebmEncoder::new(settings, 10))
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        assert_eq!(&output[..4], [0x1A, 0x45, 0xDF, 0xA3]);
+
+        // 1s of the first frame and 0.5s of the last one, at 10 fps.
+        let blocks = output
+            .windows(2)
+            .filter(|w| w[0] == SIMPLE_BLOCK as u8 && w[1] & 0x80 != 0)
+            .count();
+
+        assert!(blocks >= 15);
+
+        let duration = 1500.0f64.to_be_bytes();
+        assert!(output.windows(8).any(|w| w == duration));
+    }
+}
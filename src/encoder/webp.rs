@@ -0,0 +1,252 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use super::{changed_rect, duration_ms, full_rect, rgb_bytes, Encoder, Rect, Settings};
+
+/// Longest frame duration an ANMF chunk can hold, in milliseconds.
+const MAX_FRAME_DURATION: u64 = 0xff_ffff;
+
+/// ANMF flags: replace the frame rectangle instead of alpha-blending it, and
+/// leave it in place when the next frame is drawn.
+const ANMF_NO_BLEND: u8 = 0b10;
+
+const VP8X_ANIMATION: u8 = 0b10;
+
+pub struct WebpEncoder {
+    settings: Settings,
+    quality: Option<f32>,
+}
+
+/// An encoded frame waiting for the next frame's start time, which determines
+/// its duration.
+struct PendingFrame {
+    rect: Rect,
+    time: f64,
+    bitstream: Vec<u8>,
+}
+
+impl WebpEncoder {
+    pub fn new(settings: Settings, quality: Option<f32>) -> Self {
+        Self { settings, quality }
+    }
+
+    /// Encode the `rect` area of `image` as a still WebP and return its image
+    /// chunks (`VP8 ` or `VP8L`), ready to embed in an ANMF chunk.
+    fn encode_rect(&self, image: &ImgVec<RGBA8>, rect: Rect) -> Result<Vec<u8>> {
+        let rgb = rgb_bytes(image, rect);
+        let encoder = webp::Encoder::from_rgb(&rgb, rect.width as u32, rect.height as u32);
+
+        let still = encoder
+            .encode_simple(self.quality.is_none(), self.quality.unwrap_or(75.0))
+            .map_err(|e| anyhow!("webp encoding failed: {e:?}"))?;
+
+        image_chunks(&still)
+    }
+}
+
+impl Encoder for WebpEncoder {
+    fn encode(
+        self: Box<Self>,
+        frames: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()> {
+        let settings = &self.settings;
+        let mut progress_bar = super::progress_bar(settings);
+
+        let mut anmf_chunks = Vec::new();
+        let mut prev_image: Option<ImgVec<RGBA8>> = None;
+        let mut pending: Option<PendingFrame> = None;
+        let mut last_time = 0.0;
+
+        for (image, time) in frames {
+            if let Some(pb) = progress_bar.as_mut() {
+                pb.inc();
+            }
+
+            last_time = time;
+
+            let rect = match &prev_image {
+                Some(prev) => changed_rect(prev, &image),
+                None => Some(full_rect(&image)),
+            };
+
+            // An unchanged frame extends the display time of the pending one.
+            let Some(rect) = rect else {
+                continue;
+            };
+
+            let bitstream = self.encode_rect(&image, rect)?;
+
+            if let Some(frame) = pending.take() {
+                let duration = duration_ms(frame.time, time);
+                write_anmf(&mut anmf_chunks, &frame, duration);
+            }
+
+            pending = Some(PendingFrame {
+                rect,
+                time,
+                bitstream,
+            });
+
+            prev_image = Some(image);
+        }
+
+        if let Some(frame) = pending.take() {
+            let duration = duration_ms(frame.time, last_time + settings.last_frame_duration);
+            write_anmf(&mut anmf_chunks, &frame, duration);
+        }
+
+        let loop_count = settings.loop_count.unwrap_or(0);
+        let mut webp = Vec::new();
+        write_vp8x(&mut webp, settings.width, settings.height);
+        write_anim(&mut webp, loop_count);
+        webp.extend_from_slice(&anmf_chunks);
+
+        output.write_all(b"RIFF")?;
+        output.write_all(&(webp.len() as u32 + 4).to_le_bytes())?;
+        output.write_all(b"WEBP")?;
+        output.write_all(&webp)?;
+
+        super::finish_progress_bar(progress_bar);
+
+        Ok(())
+    }
+}
+
+/// Strip the RIFF header, and the VP8X chunk if any, from a still WebP file.
+fn image_chunks(still: &[u8]) -> Result<Vec<u8>> {
+    if still.len() < 12 || &still[0..4] != b"RIFF" || &still[8..12] != b"WEBP" {
+        return Err(anyhow!("webp encoder produced an invalid file"));
+    }
+
+    let chunks = &still[12..];
+
+    if chunks.starts_with(b"VP8X") {
+        Ok(chunks[8 + chunk_size(chunks)..].to_vec())
+    } else {
+        Ok(chunks.to_vec())
+    }
+}
+
+fn chunk_size(chunk: &[u8]) -> usize {
+    let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+
+    size + size % 2
+}
+
+fn write_chunk(buf: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    buf.extend_from_slice(fourcc);
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(payload);
+
+    if payload.len() % 2 == 1 {
+        buf.push(0);
+    }
+}
+
+fn push_u24(buf: &mut Vec<u8>, value: usize) {
+    buf.extend_from_slice(&(value as u32).to_le_bytes()[..3]);
+}
+
+fn write_vp8x(buf: &mut Vec<u8>, width: usize, height: usize) {
+    let mut payload = vec![VP8X_ANIMATION, 0, 0, 0];
+    push_u24(&mut payload, width - 1);
+    push_u24(&mut payload, height - 1);
+
+    write_chunk(buf, b"VP8X", &payload);
+}
+
+fn write_anim(buf: &mut Vec<u8>, loop_count: u16) {
+    let mut payload = vec![0, 0, 0, 0];
+    payload.extend_from_slice(&loop_count.to_le_bytes());
+
+    write_chunk(buf, b"ANIM", &payload);
+}
+
+fn write_anmf(buf: &mut Vec<u8>, frame: &PendingFrame, duration: u64) {
+    let mut payload = Vec::with_capacity(16 + frame.bitstream.len());
+    push_u24(&mut payload, frame.rect.x / 2);
+    push_u24(&mut payload, frame.rect.y / 2);
+    push_u24(&mut payload, frame.rect.width - 1);
+    push_u24(&mut payload, frame.rect.height - 1);
+    push_u24(&mut payload, duration.min(MAX_FRAME_DURATION) as usize);
+    payload.push(ANMF_NO_BLEND);
+    payload.extend_from_slice(&frame.bitstream);
+
+    write_chunk(buf, b"ANMF", &payload);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{image, settings};
+
+    #[test]
+    fn encodes_an_animated_webp_container() {
+        let settings = Settings {
+            frame_count: 2,
+            loop_count: Some(1),
+            ..settings(4, 3)
+        };
+
+        let frames = vec![
+            (image(&[0; 12], 4), 0.0),
+            (image(&[0, 0, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0], 4), 0.5),
+        ];
+
+        let mut output = Vec::new();
+
+        Box::new(WebpEncoder::new(settings, None))
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        assert_eq!(&output[0..4], b"RIFF");
+        assert_eq!(
+            output.len(),
+            8 + u32::from_le_bytes(output[4..8].try_into().unwrap()) as usize
+        );
+        assert_eq!(&output[12..16], b"VP8X");
+        assert_eq!(&output[30..34], b"ANIM");
+        assert_eq!(u16::from_le_bytes([output[42], output[43]]), 1);
+
+        let anmf_count = output.windows(4).filter(|w| w == b"ANMF").count();
+        assert_eq!(anmf_count, 2);
+    }
+
+    #[test]
+    fn unchanged_last_frames_keep_their_time() {
+        let settings = Settings {
+            frame_count: 3,
+            ..settings(2, 1)
+        };
+
+        let frames = vec![
+            (image(&[0, 0], 2), 0.0),
+            (image(&[0, 9], 2), 0.5),
+            (image(&[0, 9], 2), 1.5),
+        ];
+
+        let mut output = Vec::new();
+
+        Box::new(WebpEncoder::new(settings, None))
+            .encode(&mut frames.into_iter(), &mut output)
+            .unwrap();
+
+        let mut durations = Vec::new();
+        let mut chunks = &output[12..];
+
+        while chunks.len() >= 8 {
+            if &chunks[0..4] == b"ANMF" {
+                let d = &chunks[8 + 12..8 + 15];
+                durations.push(u32::from_le_bytes([d[0], d[1], d[2], 0]));
+            }
+
+            chunks = &chunks[8 + chunk_size(chunks)..];
+        }
+
+        assert_eq!(durations, [500, 2000]);
+    }
+}
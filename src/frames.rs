@@ -7,7 +7,8 @@
 use std::collections::VecDeque;
 
 use crate::asciicast::Event;
-use crate::terminal::{self, Snapshot};
+use crate::keys::Caption;
+use crate::terminal::{self, Snapshot, Terminal};
 
 /// A terminal state at a point in time. Holds terminal cells, not rendered
 /// pixels.
@@ -15,18 +16,21 @@ use crate::terminal::{self, Snapshot};
 pub struct Frame {
     pub time: f64,
     pub snapshot: Snapshot,
+    /// Keystroke caption drawn over the terminal, see [`crate::keys`].
+    pub keys: Option<Caption>,
 }
 
 impl Frame {
-    fn from_vt(time: f64, vt: &avt::Vt) -> Frame {
+    fn new(time: f64, terminal: &Terminal) -> Frame {
         Frame {
             time,
-            snapshot: Snapshot::from_vt(vt),
+            snapshot: Snapshot::new(terminal),
+            keys: None,
         }
     }
 
     pub fn same_visual(&self, other: &Frame) -> bool {
-        self.snapshot.same_visual(&other.snapshot)
+        self.snapshot.same_visual(&other.snapshot) && self.keys == other.keys
     }
 }
 
@@ -47,7 +51,7 @@ pub fn from_range<'a>(
     end: Option<f64>,
 ) -> impl Iterator<Item = Frame> + 'a {
     let vt = terminal::build(terminal_size);
-    let blank = Frame::from_vt(0.0, &vt);
+    let blank = Frame::new(0.0, &vt);
 
     generate_with(vt, events, RangeEmitter::new(start, end, blank))
 }
@@ -60,16 +64,48 @@ pub fn at_positions<'a>(
     positions: Vec<f64>,
 ) -> impl Iterator<Item = Frame> + 'a {
     let vt = terminal::build(terminal_size);
-    let blank = Frame::from_vt(0.0, &vt);
+    let blank = Frame::new(0.0, &vt);
 
     generate_with(vt, events, PositionEmitter::new(positions, blank))
 }
 
+/// The terminal state at `end`, or at the end of the recording, with every line
+/// that scrolled off the top kept above the viewport.
+pub fn history(events: &[Event], terminal_size: (usize, usize), end: Option<f64>) -> Snapshot {
+    let mut vt = terminal::build_with_scrollback(terminal_size);
+
+    for event in events {
+        if end.is_some_and(|end| event.time() > end) {
+            break;
+        }
+
+        match event {
+            Event::Output { data, .. } => terminal::feed_str(&mut vt, data),
+            Event::Resize { cols, rows, .. } => terminal::resize(&mut vt, (*cols, *rows)),
+            _ => {}
+        }
+    }
+
+    Snapshot::history(&vt)
+}
+
+/// The smallest canvas, in terminal cells, that fits every terminal state of the
+/// replay: the initial size grown by every resize event on the timeline.
+pub fn canvas_size(events: &[Event], terminal_size: (usize, usize)) -> (usize, usize) {
+    events
+        .iter()
+        .fold(terminal_size, |(max_cols, max_rows), event| match event {
+            Event::Resize { cols, rows, .. } => (max_cols.max(*cols), max_rows.max(*rows)),
+            _ => (max_cols, max_rows),
+        })
+}
+
 /// Replay the timeline through `vt`, feeding the emitter one candidate frame
-/// per event. Only output events mutate the terminal; marker and `Other` events
-/// still produce a candidate frame so the emitter can detect crossings.
+/// per event. Only output and resize events mutate the terminal; marker and
+/// `Other` events still produce a candidate frame so the emitter can detect
+/// crossings.
 fn generate_with<'a, E: FrameEmitter + 'a>(
-    mut vt: avt::Vt,
+    mut vt: Terminal,
     events: &'a [Event],
     mut emitter: E,
 ) -> impl Iterator<Item = Frame> + 'a {
@@ -93,11 +129,13 @@ fn generate_with<'a, E: FrameEmitter + 'a>(
             }
 
             Some(event) => {
-                if let Event::Output { data, .. } = event {
-                    terminal::feed_str(&mut vt, data);
+                match event {
+                    Event::Output { data, .. } => terminal::feed_str(&mut vt, data),
+                    Event::Resize { cols, rows, .. } => terminal::resize(&mut vt, (*cols, *rows)),
+                    _ => {}
                 }
 
-                let frame = Frame::from_vt(event.time(), &vt);
+                let frame = Frame::new(event.time(), &vt);
                 pending.extend(emitter.emit(frame, event));
             }
         }
@@ -136,13 +174,14 @@ impl FrameEmitter for RangeEmitter {
                 return out;
             }
 
-            // First event at or past the range start: enter the range. Unless an
-            // output event lands exactly on the start, emit a synthetic frame
-            // carrying the pre-start terminal state, retimed to the start.
+            // First event at or past the range start: enter the range. Unless a
+            // terminal-changing event lands exactly on the start, emit a
+            // synthetic frame carrying the pre-start terminal state, retimed to
+            // the start.
             self.started = true;
-            let output_at_start = time == self.start && matches!(event, Event::Output { .. });
+            let change_at_start = time == self.start && event.is_terminal_change();
 
-            if !output_at_start {
+            if !change_at_start {
                 if let Some(mut saved) = self.saved.take() {
                     saved.time = self.start;
                     out.push(saved);
@@ -150,7 +189,7 @@ impl FrameEmitter for RangeEmitter {
             }
         }
 
-        if self.end.is_none_or(|end| time <= end) && matches!(event, Event::Output { .. }) {
+        if self.end.is_none_or(|end| time <= end) && event.is_terminal_change() {
             out.push(frame);
         }
 
@@ -225,6 +264,10 @@ mod tests {
         }
     }
 
+    fn resize(time: f64, cols: usize, rows: usize) -> Event {
+        Event::Resize { time, cols, rows }
+    }
+
     fn times(frames: &[Frame]) -> Vec<f64> {
         frames.iter().map(|f| f.time).collect()
     }
@@ -318,6 +361,34 @@ mod tests {
         assert_eq!(texts(&frames), vec!["    ", "a   ", "ab  "]);
     }
 
+    #[test]
+    fn range_emits_a_frame_for_each_resize() {
+        let events = [output(0.5, "ab"), resize(1.0, 6, 2), output(1.5, "c")];
+        let frames = from_range(&events, (4, 1), None, None);
+
+        assert_eq!(times(&frames), vec![0.0, 0.5, 1.0, 1.5]);
+        assert_eq!(
+            texts(&frames),
+            vec!["    ", "ab  ", "ab          ", "abc         "]
+        );
+    }
+
+    #[test]
+    fn positions_apply_resizes_before_the_position() {
+        let events = [output(1.0, "a"), resize(2.0, 6, 2), output(3.0, "b")];
+        let frames = at_positions(&events, (4, 1), vec![2.5]);
+
+        assert_eq!(texts(&frames), vec!["a           "]);
+    }
+
+    #[test]
+    fn canvas_size_is_the_largest_size_reached() {
+        let events = [resize(1.0, 100, 20), output(1.5, "a"), resize(2.0, 60, 40)];
+
+        assert_eq!(canvas_size(&events, (80, 24)), (100, 40));
+        assert_eq!(canvas_size(&[], (80, 24)), (80, 24));
+    }
+
     #[test]
     fn positions_capture_seek_state_at_each_timestamp() {
         let events = [output(1.0, "a"), output(2.0, "b"), output(3.0, "c")];
@@ -360,4 +431,31 @@ mod tests {
 
         assert_eq!(texts(&frames), vec!["abc "]);
     }
+
+    #[test]
+    fn history_keeps_scrolled_off_lines_up_to_end() {
+        let events = [
+            output(1.0, "one\r\ntwo\r\nthree\r\n"),
+            output(2.0, "four\r\n\r\n"),
+            output(3.0, "five"),
+        ];
+
+        let lines = |snapshot: Snapshot| {
+            snapshot
+                .lines
+                .iter()
+                .map(|l| l.text().trim_end().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            lines(history(&events, (5, 2), None)),
+            ["one", "two", "three", "four", "", "five"]
+        );
+
+        assert_eq!(
+            lines(history(&events, (5, 2), Some(2.5))),
+            ["one", "two", "three", "four"]
+        );
+    }
 }
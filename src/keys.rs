@@ -0,0 +1,547 @@
+//! Keystroke overlay from input events.
+//!
+//! Keys typed in quick succession are shown together as a caption in a corner
+//! of the frame, which fades out once typing stops. Control characters and
+//! escape sequences are shown by name, e.g. `Enter`, `Ctrl-C` or `Up`.
+
+use std::collections::HashMap;
+use std::iter::Peekable;
+
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use crate::asciicast::Event;
+use crate::frames::Frame;
+use crate::renderer::Renderer;
+use crate::terminal::{self, Snapshot};
+use crate::Corner;
+
+/// How long the caption stays fully visible after the last key.
+const HOLD: f64 = 1.0;
+
+/// How long the caption then takes to fade out, in `FADE_STEPS` steps.
+const FADE: f64 = 0.5;
+const FADE_STEPS: u8 = 4;
+
+/// Longest caption, in characters. Older keys are dropped first.
+const MAX_LEN: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    Named(String),
+}
+
+/// The keys typed at some point, as text with key names in bold, and how
+/// opaque to draw it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Caption {
+    pub text: String,
+    pub opacity: f32,
+}
+
+/// Input events on the output timeline.
+pub struct Keystrokes(Vec<(f64, Vec<Key>)>);
+
+impl Keystrokes {
+    /// Keys from the input events, timed relative to `offset`, e.g. the start
+    /// of a range selection.
+    pub fn from_events(events: &[Event], offset: f64) -> Self {
+        let keys = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Input { time, data } => Some((time - offset, parse(data))),
+                _ => None,
+            })
+            .collect();
+
+        Self(keys)
+    }
+
+    /// The caption shown at `time`, if any.
+    pub fn caption(&self, time: f64) -> Option<Caption> {
+        let count = self.0.partition_point(|(t, _)| *t <= time);
+        let last = count.checked_sub(1)?;
+
+        // Frame times are sums of floats, so allow for rounding at a step.
+        let age = time - self.0[last].0 + 1e-9;
+        let step = ((age - HOLD).max(0.0) / step_duration()).floor();
+
+        if step >= FADE_STEPS as f64 {
+            return None;
+        }
+
+        // Keys typed while the caption was still showing join it.
+        let mut first = last;
+
+        while first > 0 && self.0[first].0 - self.0[first - 1].0 < HOLD + FADE {
+            first -= 1;
+        }
+
+        let keys = self.0[first..count].iter().flat_map(|(_, keys)| keys);
+
+        Some(Caption {
+            text: caption_text(keys),
+            opacity: 1.0 - step as f32 / FADE_STEPS as f32,
+        })
+    }
+
+    /// Times at which the caption changes: every key and every fade step.
+    fn changes(&self) -> Vec<f64> {
+        let mut times = Vec::new();
+
+        for (i, (time, _)) in self.0.iter().enumerate() {
+            times.push(*time);
+            let next = self.0.get(i + 1).map(|(t, _)| *t);
+
+            for step in 1..=FADE_STEPS {
+                let change = time + HOLD + step as f64 * step_duration();
+
+                if next.is_some_and(|next| change >= next) {
+                    break;
+                }
+
+                times.push(change);
+            }
+        }
+
+        times
+    }
+}
+
+fn step_duration() -> f64 {
+    FADE / FADE_STEPS as f64
+}
+
+/// Set the caption of every frame, adding frames wherever it changes between
+/// them, e.g. as it fades out. Changes while the last frame is shown get frames
+/// too; returns how long the new last frame is shown.
+pub fn overlay(
+    frames: Vec<Frame>,
+    keystrokes: &Keystrokes,
+    last_frame_duration: f64,
+) -> (Vec<Frame>, f64) {
+    let Some(end) = frames.last().map(|f| f.time + last_frame_duration) else {
+        return (frames, last_frame_duration);
+    };
+
+    let mut changes = keystrokes.changes().into_iter().peekable();
+    let mut frames = frames.into_iter().peekable();
+    let mut out: Vec<Frame> = Vec::new();
+
+    while let Some(mut frame) = frames.next() {
+        let next_time = frames.peek().map_or(end, |f| f.time);
+        frame.keys = keystrokes.caption(frame.time);
+
+        while let Some(time) = changes.next_if(|t| *t < next_time) {
+            if time <= frame.time {
+                continue;
+            }
+
+            let keys = keystrokes.caption(time);
+
+            if keys != frame.keys {
+                let mut extra = frame.clone();
+                extra.time = time;
+                extra.keys = keys;
+
+                out.push(frame);
+                frame = extra;
+            }
+        }
+
+        out.push(frame);
+    }
+
+    let last_frame_duration = end - out.last().unwrap().time;
+
+    (out, last_frame_duration)
+}
+
+/// The keys in an input event's data.
+pub fn parse(data: &str) -> Vec<Key> {
+    let mut chars = data.chars().peekable();
+    let mut keys = Vec::new();
+
+    while let Some(c) = chars.next() {
+        let key = if c != '\x1b' {
+            key(c)
+        } else {
+            match chars.next() {
+                None => named("Esc"),
+                Some('[' | 'O') if chars.peek().is_some() => escape_sequence(&mut chars),
+                Some(c) => match key(c) {
+                    Key::Char(c) => Key::Named(format!("Alt-{c}")),
+                    Key::Named(name) => Key::Named(format!("Alt-{name}")),
+                },
+            }
+        };
+
+        keys.push(key);
+    }
+
+    keys
+}
+
+fn key(c: char) -> Key {
+    match c {
+        '\r' | '\n' => named("Enter"),
+        '\t' => named("Tab"),
+        '\x7f' | '\x08' => named("Backspace"),
+        '\x1b' => named("Esc"),
+        '\0' => named("Ctrl-Space"),
+        '\x01'..='\x1f' => Key::Named(format!("Ctrl-{}", (c as u8 + b'@') as char)),
+        c if c.is_control() => Key::Char('\u{fffd}'),
+        c => Key::Char(c),
+    }
+}
+
+/// A CSI or SS3 sequence, after its introducer, as sent by cursor, editing and
+/// function keys, optionally with xterm modifiers.
+fn escape_sequence(chars: &mut Peekable<impl Iterator<Item = char>>) -> Key {
+    let mut params = String::new();
+
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == ';') {
+        params.push(c);
+    }
+
+    let (code, modifiers) = match params.split_once(';') {
+        Some((code, modifiers)) => (code, modifiers.parse::<u8>().unwrap_or(1)),
+        None => (params.as_str(), 1),
+    };
+
+    let name = match (chars.next(), code) {
+        (Some('A'), _) => "Up",
+        (Some('B'), _) => "Down",
+        (Some('C'), _) => "Right",
+        (Some('D'), _) => "Left",
+        (Some('H'), _) | (Some('~'), "1" | "7") => "Home",
+        (Some('F'), _) | (Some('~'), "4" | "8") => "End",
+        (Some('Z'), _) => "Shift-Tab",
+        (Some('P'), _) => "F1",
+        (Some('Q'), _) => "F2",
+        (Some('R'), _) => "F3",
+        (Some('S'), _) => "F4",
+        (Some('~'), "2") => "Insert",
+        (Some('~'), "3") => "Delete",
+        (Some('~'), "5") => "PageUp",
+        (Some('~'), "6") => "PageDown",
+        (Some('~'), "15") => "F5",
+        (Some('~'), "17") => "F6",
+        (Some('~'), "18") => "F7",
+        (Some('~'), "19") => "F8",
+        (Some('~'), "20") => "F9",
+        (Some('~'), "21") => "F10",
+        (Some('~'), "23") => "F11",
+        (Some('~'), "24") => "F12",
+        _ => "Esc",
+    };
+
+    // xterm sends 1 plus a bit mask of Shift (1), Alt (2) and Ctrl (4).
+    let mask = modifiers.saturating_sub(1);
+    let mut prefix = String::new();
+
+    for (bit, modifier) in [(4, "Ctrl-"), (2, "Alt-"), (1, "Shift-")] {
+        if mask & bit != 0 {
+            prefix.push_str(modifier);
+        }
+    }
+
+    Key::Named(format!("{prefix}{name}"))
+}
+
+fn named(name: &str) -> Key {
+    Key::Named(name.to_owned())
+}
+
+/// Typed characters as they are and key names in bold, set apart by spaces.
+/// Only the last `MAX_LEN` characters are kept.
+fn caption_text<'a>(keys: impl Iterator<Item = &'a Key>) -> String {
+    let mut parts: Vec<(String, bool)> = Vec::new();
+    let mut prev_named = None;
+
+    for key in keys {
+        let named = matches!(key, Key::Named(_));
+
+        if prev_named.is_some_and(|prev| prev || named) {
+            parts.push((" ".to_owned(), false));
+        }
+
+        match key {
+            Key::Char(c) => parts.push((c.to_string(), false)),
+            Key::Named(name) => parts.push((name.clone(), true)),
+        }
+
+        prev_named = Some(named);
+    }
+
+    let mut len = 0;
+    let mut start = parts.len();
+
+    while start > 0 && len + parts[start - 1].0.chars().count() <= MAX_LEN {
+        start -= 1;
+        len += parts[start].0.chars().count();
+    }
+
+    let mut text = if start > 0 {
+        String::from("…")
+    } else {
+        String::new()
+    };
+
+    for (part, bold) in &parts[start..] {
+        if *bold {
+            text.push_str(&format!("\x1b[1m{part}\x1b[22m"));
+        } else {
+            text.push_str(part);
+        }
+    }
+
+    text
+}
+
+/// Draws captions over rendered frames, keeping the image of every caption
+/// text drawn so far.
+pub struct Overlay {
+    corner: Corner,
+    terminal_size: (usize, usize),
+    images: HashMap<String, ImgVec<RGBA8>>,
+}
+
+impl Overlay {
+    pub fn new(corner: Corner, terminal_size: (usize, usize)) -> Self {
+        Self {
+            corner,
+            terminal_size,
+            images: HashMap::new(),
+        }
+    }
+
+    /// Blend `caption` into `image`, a frame rendered by `renderer`.
+    pub fn draw(
+        &mut self,
+        image: &mut ImgVec<RGBA8>,
+        caption: &Caption,
+        renderer: &mut dyn Renderer,
+    ) {
+        let (col_width, row_height) = cell_size(renderer, self.terminal_size);
+
+        let caption_image = self
+            .images
+            .entry(caption.text.clone())
+            .or_insert_with(|| render(renderer, self.terminal_size, &caption.text));
+
+        let margin = (
+            col_width.round() as usize,
+            (row_height / 2.0).round() as usize,
+        );
+        blend(image, caption_image, self.corner, margin, caption.opacity);
+    }
+}
+
+/// Renderers lay the terminal out with a column of margin on either side and
+/// half a row above and below.
+fn cell_size(renderer: &dyn Renderer, terminal_size: (usize, usize)) -> (f64, f64) {
+    let (width, height) = renderer.pixel_size();
+
+    (
+        width as f64 / (terminal_size.0 + 2) as f64,
+        height as f64 / (terminal_size.1 + 1) as f64,
+    )
+}
+
+/// Render `text` in inverse video on the first terminal line and crop the
+/// image to it, plus a space on either side.
+fn render(renderer: &mut dyn Renderer, terminal_size: (usize, usize), text: &str) -> ImgVec<RGBA8> {
+    let mut vt = terminal::build(terminal_size);
+    terminal::feed_str(&mut vt, "\x1b[?7l\x1b[7m ");
+    terminal::feed_str(&mut vt, text);
+    terminal::feed_str(&mut vt, " ");
+
+    let mut snapshot = Snapshot::new(&vt);
+    snapshot.cursor = None;
+
+    let cols = snapshot.lines[0]
+        .cells()
+        .iter()
+        .take_while(|cell| cell.pen().is_inverse())
+        .count();
+
+    let image = renderer.render(&snapshot);
+    let (col_width, row_height) = cell_size(renderer, terminal_size);
+
+    let x = col_width.round() as usize;
+    let y = (row_height / 2.0).round() as usize;
+    let width = ((cols + 1) as f64 * col_width).round() as usize - x;
+    let height = (row_height * 1.5).round() as usize - y;
+
+    let (buf, width, height) = image.sub_image(x, y, width, height).to_contiguous_buf();
+
+    ImgVec::new(buf.into_owned(), width, height)
+}
+
+/// Blend `caption` over `image` at `opacity`, in `corner`, `margin` pixels in
+/// from the sides. A caption larger than the image is cut off.
+fn blend(
+    image: &mut ImgVec<RGBA8>,
+    caption: &ImgVec<RGBA8>,
+    corner: Corner,
+    (margin_x, margin_y): (usize, usize),
+    opacity: f32,
+) {
+    let width = caption.width().min(image.width().saturating_sub(margin_x));
+    let height = caption
+        .height()
+        .min(image.height().saturating_sub(margin_y));
+
+    let x = match corner {
+        Corner::TopLeft | Corner::BottomLeft => margin_x,
+        Corner::TopRight | Corner::BottomRight => image.width() - margin_x - width,
+    };
+
+    let y = match corner {
+        Corner::TopLeft | Corner::TopRight => margin_y,
+        Corner::BottomLeft | Corner::BottomRight => image.height() - margin_y - height,
+    };
+
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * opacity).round() as u8;
+
+    for (row, caption_row) in image
+        .sub_image_mut(x, y, width, height)
+        .rows_mut()
+        .zip(caption.rows())
+    {
+        for (pixel, c) in row.iter_mut().zip(caption_row) {
+            pixel.r = mix(pixel.r, c.r);
+            pixel.g = mix(pixel.g, c.g);
+            pixel.b = mix(pixel.b, c.b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(time: f64, data: &str) -> Event {
+        Event::Input {
+            time,
+            data: data.to_owned(),
+        }
+    }
+
+    fn frame(time: f64) -> Frame {
+        Frame {
+            time,
+            snapshot: Snapshot {
+                lines: Vec::new(),
+                decorations: Vec::new(),
+                graphemes: Vec::new(),
+                cursor: None,
+                cursor_style: Default::default(),
+                blink_hidden: false,
+            },
+            keys: None,
+        }
+    }
+
+    #[test]
+    fn names_special_keys() {
+        let keys = parse("a\r\x03\x1b[A\x1b[1;5C\x1b[3~\x1bOP\x1bb\x1b");
+
+        let names: Vec<String> = keys
+            .iter()
+            .map(|key| match key {
+                Key::Char(c) => c.to_string(),
+                Key::Named(name) => name.clone(),
+            })
+            .collect();
+
+        assert_eq!(
+            names,
+            [
+                "a",
+                "Enter",
+                "Ctrl-C",
+                "Up",
+                "Ctrl-Right",
+                "Delete",
+                "F1",
+                "Alt-b",
+                "Esc"
+            ]
+        );
+    }
+
+    #[test]
+    fn caption_groups_keys_and_fades_out() {
+        let events = [
+            input(10.0, "l"),
+            input(10.5, "s"),
+            input(11.0, "\r"),
+            input(20.0, "\x03"),
+        ];
+
+        let keystrokes = Keystrokes::from_events(&events, 10.0);
+
+        assert_eq!(keystrokes.caption(-0.5), None);
+
+        let caption = keystrokes.caption(1.5).unwrap();
+        assert_eq!(caption.text, "ls \x1b[1mEnter\x1b[22m");
+        assert_eq!(caption.opacity, 1.0);
+
+        assert_eq!(keystrokes.caption(2.25).unwrap().opacity, 0.5);
+        assert_eq!(keystrokes.caption(2.5), None);
+        assert_eq!(
+            keystrokes.caption(10.0).unwrap().text,
+            "\x1b[1mCtrl-C\x1b[22m"
+        );
+    }
+
+    #[test]
+    fn long_captions_keep_the_latest_keys() {
+        let keys = parse(&"x".repeat(40));
+
+        assert_eq!(caption_text(keys.iter()), format!("…{}", "x".repeat(32)));
+    }
+
+    #[test]
+    fn overlay_adds_frames_for_fade_steps() {
+        let keystrokes = Keystrokes::from_events(&[input(0.5, "a")], 0.0);
+        let (frames, last_frame_duration) = overlay(vec![frame(0.0), frame(1.0)], &keystrokes, 3.0);
+
+        let times: Vec<f64> = frames.iter().map(|f| f.time).collect();
+        assert_eq!(times, [0.0, 0.5, 1.0, 1.625, 1.75, 1.875, 2.0]);
+        assert_eq!(last_frame_duration, 2.0);
+
+        let opacities: Vec<Option<f32>> = frames
+            .iter()
+            .map(|f| f.keys.as_ref().map(|k| k.opacity))
+            .collect();
+
+        assert_eq!(
+            opacities,
+            [
+                None,
+                Some(1.0),
+                Some(1.0),
+                Some(0.75),
+                Some(0.5),
+                Some(0.25),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn blends_caption_into_corner() {
+        let mut image = ImgVec::new(vec![RGBA8::new(0, 0, 0, 255); 16], 4, 4);
+        let caption = ImgVec::new(vec![RGBA8::new(200, 200, 200, 255); 2], 2, 1);
+
+        blend(&mut image, &caption, Corner::BottomRight, (1, 1), 0.5);
+
+        let values: Vec<u8> = image.buf().iter().map(|p| p.r).collect();
+
+        assert_eq!(values, [0, 0, 0, 0, 0, 0, 0, 0, 0, 100, 100, 0, 0, 0, 0, 0]);
+    }
+}
@@ -1,24 +1,37 @@
 mod asciicast;
+mod budget;
+mod chapters;
+mod encoder;
 mod fonts;
 mod frames;
+mod keys;
 mod output;
 mod renderer;
+mod report;
 mod selection;
 mod terminal;
 mod theme;
 mod timeline;
 
 use std::fmt::{Debug, Display};
+use std::fs::{self, File};
 use std::io::{BufRead, Write};
-use std::{thread, time::Instant};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use clap::ValueEnum;
-use log::{info, warn};
+use imgref::ImgVec;
+use log::info;
+use rgb::{RGB8, RGBA8};
 
 use crate::asciicast::Asciicast;
+use crate::renderer::Renderer as _;
 
-pub use crate::selection::SelectionSpec;
+pub use crate::selection::{SelectionSpec, TimelinePosition};
+pub use crate::terminal::CursorShape;
 
 pub const DEFAULT_BOLD_IS_BRIGHT: bool = false;
 pub const DEFAULT_FONT_HINTING: bool = true;
@@ -30,15 +43,29 @@ pub const FULL_FONT_AA_LEVELS: u16 = 256;
 pub const DEFAULT_FONT_AA_LEVELS: u16 = 6;
 pub const DEFAULT_FONT_SIZE: usize = 16;
 pub const DEFAULT_FPS_CAP: u8 = 30;
+pub const DEFAULT_GIF_SCALE: f64 = 1.0;
 pub const DEFAULT_LAST_FRAME_DURATION: f64 = 3.0;
 pub const DEFAULT_LINE_HEIGHT: f64 = 1.4;
+pub const DEFAULT_LOOP_PAUSE: f64 = 0.0;
+#[deprecated(note = "use `Config::loop_count`")]
 pub const DEFAULT_NO_LOOP: bool = false;
 pub const DEFAULT_SPEED: f64 = 1.0;
 pub const DEFAULT_IDLE_TIME_LIMIT: f64 = 5.0;
 
+/// Rendered images waiting for each encoder when there are several outputs.
+const ENCODER_QUEUE_LEN: usize = 4;
+
 pub struct Config {
     pub bold_is_bright: bool,
     pub cols: Option<usize>,
+    pub contact_sheet: Option<ContactSheet>,
+    /// Blink a blinking cursor style while the terminal is idle.
+    pub cursor_blink: bool,
+    /// Color of the cursor, drawn with the text under a block cursor in the
+    /// cell's background color, instead of swapping the cell's colors.
+    pub cursor_color: Option<RGB8>,
+    /// Cursor shape to draw regardless of the one the recording selects.
+    pub cursor_shape: Option<CursorShape>,
     pub emoji_font_family: String,
     pub font_size: usize,
     pub font_dirs: Vec<String>,
@@ -46,46 +73,184 @@ pub struct Config {
     pub font_aa_levels: u16,
     pub font_hinting: bool,
     pub fps_cap: u8,
+    /// Write the final terminal state with all of its scrollback as a single
+    /// PNG image instead of the selected frames.
+    pub full_history: bool,
+    pub gif_quality: GifQuality,
+    /// Factor GIF output is scaled down by, up to 1.
+    pub gif_scale: f64,
+    /// Draw no cursor at all.
+    pub hide_cursor: bool,
     pub idle_time_limit: Option<f64>,
+    /// Show recently typed keys, from the recording's input events, in the
+    /// given corner of the frame.
+    pub keystrokes: Option<Corner>,
     pub last_frame_duration: f64,
     pub line_height: f64,
+    pub poster: Option<Poster>,
+    /// Size limit for GIF output in bytes, fitted by lowering quality, frame
+    /// rate and then output scale as needed.
+    pub max_size: Option<u64>,
+    /// Number of times the animation plays, or `None` to loop forever.
+    pub loop_count: Option<u16>,
+    /// Play the animation once, overriding `loop_count`.
+    #[deprecated(note = "use `loop_count: Some(1)`")]
     pub no_loop: bool,
+    /// Extra time the last frame is shown before the animation restarts.
+    pub loop_pause: f64,
+    /// Play the frames forward and then backward.
+    pub ping_pong: bool,
     pub renderer: Renderer,
+    /// Write a JSON summary of the run: theme, fonts, sizes, frame count,
+    /// timings and warnings.
+    pub report: Option<PathBuf>,
     pub rows: Option<usize>,
     pub selection: SelectionSpec,
     pub speed: f64,
     pub text_font_family: String,
+    /// How text with the blink attribute (SGR 5 and 6) is drawn.
+    pub text_blink: TextBlink,
     pub theme: Option<Theme>,
     pub show_progress_bar: bool,
+    pub webp_quality: Option<f32>,
 }
 
 impl Default for Config {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
             bold_is_bright: DEFAULT_BOLD_IS_BRIGHT,
             cols: None,
+            contact_sheet: None,
+            cursor_blink: false,
+            cursor_color: None,
+            cursor_shape: None,
             emoji_font_family: String::from(DEFAULT_EMOJI_FONT_FAMILY),
             font_dirs: vec![],
             font_family: None,
             font_aa_levels: DEFAULT_FONT_AA_LEVELS,
             font_size: DEFAULT_FONT_SIZE,
             fps_cap: DEFAULT_FPS_CAP,
+            full_history: false,
             font_hinting: DEFAULT_FONT_HINTING,
+            gif_quality: GifPreset::default().into(),
+            gif_scale: DEFAULT_GIF_SCALE,
+            hide_cursor: false,
             idle_time_limit: None,
+            keystrokes: None,
             last_frame_duration: DEFAULT_LAST_FRAME_DURATION,
             line_height: DEFAULT_LINE_HEIGHT,
+            poster: None,
+            max_size: None,
+            loop_count: None,
             no_loop: DEFAULT_NO_LOOP,
+            loop_pause: DEFAULT_LOOP_PAUSE,
+            ping_pong: false,
             renderer: Default::default(),
+            report: None,
             rows: None,
             selection: SelectionSpec::default(),
             speed: DEFAULT_SPEED,
             text_font_family: String::from(DEFAULT_TEXT_FONT_FAMILY),
+            text_blink: TextBlink::default(),
             theme: Default::default(),
             show_progress_bar: true,
+            webp_quality: None,
         }
     }
 }
 
+/// A still PNG image of a single position, written alongside the outputs. The
+/// position is independent of the selection.
+pub struct Poster {
+    pub position: TimelinePosition,
+    pub path: PathBuf,
+}
+
+/// Lay the selected frames out in a grid on a single PNG image.
+pub struct ContactSheet {
+    /// Number of grid columns; a near-square grid when `None`.
+    pub columns: Option<usize>,
+    /// Caption each frame with its timestamp and marker label.
+    pub captions: bool,
+}
+
+/// gifski encoder settings. Qualities range from 1 to 100.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GifQuality {
+    /// Palette quantization quality.
+    pub quality: u8,
+    /// Lossy LZW compression quality; 100 is lossless, and lower values trade
+    /// noise and dithering artifacts for smaller files.
+    pub lossy_quality: u8,
+    /// Quality of moving areas; lower values denoise and dither them less.
+    pub motion_quality: u8,
+    /// Quantize frames faster at some cost in quality. The slow path also
+    /// spends extra effort on blurring and remapping.
+    pub fast: bool,
+}
+
+/// Named sets of GIF encoder settings.
+#[derive(Clone, Copy, Debug, ValueEnum, Default, PartialEq)]
+pub enum GifPreset {
+    /// Lossy compression and lower motion quality for the smallest files
+    Small,
+    /// Lossless compression in gifski's fast mode
+    #[default]
+    Balanced,
+    /// Lossless compression in gifski's slow mode, for the highest quality
+    Best,
+}
+
+impl From<GifPreset> for GifQuality {
+    fn from(preset: GifPreset) -> Self {
+        match preset {
+            GifPreset::Small => GifQuality {
+                quality: 70,
+                lossy_quality: 40,
+                motion_quality: 50,
+                fast: true,
+            },
+
+            GifPreset::Balanced => GifQuality {
+                quality: 100,
+                lossy_quality: 100,
+                motion_quality: 100,
+                fast: true,
+            },
+
+            GifPreset::Best => GifQuality {
+                quality: 100,
+                lossy_quality: 100,
+                motion_quality: 100,
+                fast: false,
+            },
+        }
+    }
+}
+
+/// Where the keystroke overlay goes.
+#[derive(Clone, Copy, Debug, ValueEnum, Default, PartialEq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// How text with the blink attribute is drawn.
+#[derive(Clone, Copy, Debug, ValueEnum, Default, PartialEq)]
+pub enum TextBlink {
+    /// Like any other text
+    #[default]
+    Static,
+    /// Hidden and shown again every half second
+    Animate,
+    /// In bold
+    Bold,
+}
+
 #[derive(Clone, ValueEnum, Default, PartialEq)]
 pub enum Renderer {
     #[default]
@@ -94,6 +259,73 @@ pub enum Renderer {
     Resvg,
 }
 
+#[derive(Clone, Copy, Debug, ValueEnum, Default, PartialEq)]
+pub enum Format {
+    #[default]
+    Gif,
+    Webp,
+    Apng,
+    Svg,
+    /// asciicast v3 recording, written back out trimmed and retimed rather
+    /// than rendered
+    Cast,
+    /// Plain-text transcript of the frames, with timestamps
+    Txt,
+    /// Transcript of the frames with ANSI text attributes, with timestamps
+    Ansi,
+    /// The cells, pen attributes and cursor of every frame as JSON
+    Json,
+    /// Single-page HTML player with the rendered frames inline
+    Html,
+    /// WebVTT chapters from the recording's markers
+    Vtt,
+    /// SRT subtitles from the recording's markers
+    Srt,
+    /// AV1 video in a WebM container (requires the `video` cargo feature)
+    Webm,
+    /// A single PNG image, or a directory of numbered PNG images when more than
+    /// one frame is selected.
+    Png,
+}
+
+impl Format {
+    /// Guess the output format from a file name extension. A directory path
+    /// selects PNG frames.
+    pub fn from_path(path: &str) -> Option<Self> {
+        if is_dir_path(Path::new(path)) {
+            return Some(Format::Png);
+        }
+
+        let ext = Path::new(path).extension()?.to_str()?;
+
+        match ext.to_lowercase().as_str() {
+            "gif" => Some(Format::Gif),
+            "webp" => Some(Format::Webp),
+            "apng" => Some(Format::Apng),
+            "svg" => Some(Format::Svg),
+            "cast" => Some(Format::Cast),
+            "txt" => Some(Format::Txt),
+            "ans" | "ansi" => Some(Format::Ansi),
+            "json" => Some(Format::Json),
+            "html" | "htm" => Some(Format::Html),
+            "vtt" => Some(Format::Vtt),
+            "srt" => Some(Format::Srt),
+            "webm" => Some(Format::Webm),
+            "png" => Some(Format::Png),
+            _ => None,
+        }
+    }
+
+    /// Whether the format plays the frames over time, rather than holding
+    /// text or still images.
+    fn is_animated(self) -> bool {
+        matches!(
+            self,
+            Format::Gif | Format::Webp | Format::Apng | Format::Svg | Format::Html | Format::Webm
+        )
+    }
+}
+
 #[derive(Clone, Debug, ValueEnum, Default)]
 pub enum Theme {
     Asciinema,
@@ -153,7 +385,95 @@ impl Display for Theme {
     }
 }
 
-pub fn run<I: BufRead, O: Write + Send>(input: I, output: O, config: Config) -> Result<()> {
+/// Whether `path` names a directory, existing or to be created.
+fn is_dir_path(path: &Path) -> bool {
+    path.as_os_str()
+        .to_string_lossy()
+        .ends_with(['/', std::path::MAIN_SEPARATOR])
+        || path.is_dir()
+}
+
+/// An output file and the format to write it in.
+pub struct Output {
+    /// A file, or a directory for a sequence of PNG frames.
+    pub path: PathBuf,
+    pub format: Format,
+}
+
+/// Convert the recording read from `input` and write the result to every one
+/// of `outputs`. The recording is replayed and each frame rendered only once,
+/// however many outputs there are.
+pub fn run_outputs<I: BufRead>(input: I, outputs: &[Output], config: Config) -> Result<()> {
+    let report_path = config.report.clone();
+    let poster_path = config.poster.as_ref().map(|poster| poster.path.clone());
+    let mut report = report::Report::default();
+
+    convert(input, outputs, config, &mut report, None)?;
+
+    if let Some(path) = report_path {
+        report.add_outputs(outputs, poster_path.as_deref())?;
+        write_file(&path, |file| report.write(file))?;
+    }
+
+    Ok(())
+}
+
+/// Convert the recording read from `input` to a GIF written to `output`.
+#[deprecated(note = "use `run_outputs`, which writes any number of output files")]
+pub fn run<I: BufRead, O: Write + Send>(input: I, mut output: O, config: Config) -> Result<()> {
+    let outputs = [Output {
+        path: PathBuf::new(),
+        format: Format::Gif,
+    }];
+
+    convert(
+        input,
+        &outputs,
+        config,
+        &mut report::Report::default(),
+        Some(&mut output),
+    )
+}
+
+/// Where an encoder writes its output.
+enum Sink<'a> {
+    /// A file, removed when encoding fails.
+    File(PathBuf),
+    /// A writer of the library's caller.
+    Writer(&'a mut (dyn Write + Send)),
+}
+
+impl Sink<'_> {
+    fn write(self, write: impl FnOnce(&mut (dyn Write + Send)) -> Result<()>) -> Result<()> {
+        match self {
+            Sink::File(path) => write_file(&path, |file| write(file)),
+            Sink::Writer(writer) => write(writer),
+        }
+    }
+}
+
+/// With `writer`, the first GIF output goes there instead of to its path.
+fn convert<I: BufRead>(
+    input: I,
+    outputs: &[Output],
+    config: Config,
+    report: &mut report::Report,
+    mut writer: Option<&mut (dyn Write + Send)>,
+) -> Result<()> {
+    let has_format = |formats: &[Format]| outputs.iter().any(|o| formats.contains(&o.format));
+
+    if has_format(&[Format::Webm]) && !cfg!(feature = "video") {
+        bail!("WebM output needs agg built with the `video` cargo feature");
+    }
+
+    if config.contact_sheet.is_some() && outputs.iter().any(|o| o.format != Format::Png) {
+        bail!("a contact sheet is a PNG image, use a .png output path");
+    }
+
+    if config.full_history && outputs.iter().any(|o| o.format != Format::Png) {
+        bail!("the full history is a PNG image, use a .png output path");
+    }
+
     let Asciicast { header, events, .. } = asciicast::open(input)?;
 
     if header.term_cols == 0 || header.term_rows == 0 {
@@ -176,36 +496,268 @@ pub fn run<I: BufRead, O: Write + Send>(input: I, output: O, config: Config) ->
 
     let events = timeline::limit_idle_time(events, itl);
     let events = timeline::accelerate(events, config.speed);
+    let events = timeline::pin_terminal_size(events, config.cols, config.rows);
     let events = events.collect::<Result<Vec<_>>>()?;
+    let canvas_size = frames::canvas_size(&events, terminal_size);
+    report.terminal_size = Some(terminal_size.into());
+    report.canvas_size = Some(canvas_size.into());
 
     let summary = timeline::Summary::from_events(&events);
     let plan = selection::resolve(&config.selection, &summary)?;
 
-    let frames: Vec<frames::Frame> = match plan {
+    let history = match (&plan, config.full_history) {
+        (_, false) => None,
+
+        (selection::SelectionPlan::Range { start: None, end }, true) => {
+            Some(frames::history(&events, terminal_size, *end))
+        }
+
+        _ => bail!("the full history runs from the start of the recording, select its end with e.g. --select ..20"),
+    };
+
+    let poster = config
+        .poster
+        .as_ref()
+        .map(|poster| {
+            selection::resolve_position(&poster.position, &summary).map(|time| (&poster.path, time))
+        })
+        .transpose()?;
+
+    let theme_opt = config
+        .theme
+        .or_else(|| header.term_theme.map(Theme::Embedded));
+
+    if has_format(&[Format::Cast]) {
+        let selection::SelectionPlan::Range { start, end } = plan else {
+            bail!("asciicast output needs a range selection, e.g. --select 5..20");
+        };
+
+        let (size, events) = timeline::trim(&events, terminal_size, start, end);
+
+        // Idle time limiting has already been applied to the event times.
+        let header = asciicast::Header {
+            term_cols: size.0 as u16,
+            term_rows: size.1 as u16,
+            term_theme: theme_opt.clone().map(TryInto::try_into).transpose()?,
+            idle_time_limit: None,
+        };
+
+        for output in outputs.iter().filter(|o| o.format == Format::Cast) {
+            write_file(&output.path, |file| {
+                asciicast::write(file, &header, &events)
+            })?;
+        }
+
+        if poster.is_none() && outputs.iter().all(|o| o.format == Format::Cast) {
+            return Ok(());
+        }
+    }
+
+    let captions: Vec<String> = match (&plan, &config.contact_sheet) {
+        (selection::SelectionPlan::Range { .. }, Some(_)) => {
+            bail!("a contact sheet needs a list of positions, e.g. --select markers or --select 5,10,20");
+        }
+
+        (selection::SelectionPlan::Positions(positions), Some(sheet)) if sheet.captions => {
+            positions
+                .iter()
+                .map(|time| caption(*time, summary.markers()))
+                .collect()
+        }
+
+        _ => Vec::new(),
+    };
+
+    // Applied before deduping, so that cursor moves alone don't add frames
+    // while the cursor is hidden.
+    let cursor_options = |mut frame: frames::Frame| {
+        if config.hide_cursor {
+            frame.snapshot.cursor = None;
+        }
+
+        if let Some(shape) = config.cursor_shape {
+            frame.snapshot.cursor_style.shape = shape;
+        }
+
+        frame
+    };
+
+    let (frames, last_frame_duration): (Vec<frames::Frame>, f64) = match &plan {
         // Range selections produce time-based animation frames: dedupe duplicate
-        // states, normalize the first frame to t=0, then cap FPS.
+        // states, normalize the first frame to t=0, then cap FPS. A blinking
+        // cursor, blinking text and keystroke captions fading out over time
+        // add frames of their own.
         selection::SelectionPlan::Range { start, end } => {
-            let frames = frames::from_range(&events, terminal_size, start, end);
+            let frames =
+                frames::from_range(&events, terminal_size, *start, *end).map(cursor_options);
             let frames = output::dedupe_visual_changes(frames);
             let frames = output::adjust_timeline_timestamps(frames);
-            output::cap_fps(frames, config.fps_cap).collect()
+            let mut frames: Vec<frames::Frame> = frames.collect();
+            let mut last_frame_duration = config.last_frame_duration;
+
+            if config.cursor_blink {
+                (frames, last_frame_duration) = output::blink_cursor(frames, last_frame_duration);
+            }
+
+            // After the cursor, which would otherwise restart its blink on
+            // every text blink frame.
+            if config.text_blink == TextBlink::Animate {
+                (frames, last_frame_duration) = output::blink_text(frames, last_frame_duration);
+            }
+
+            if config.keystrokes.is_some() {
+                let keystrokes = keys::Keystrokes::from_events(&events, start.unwrap_or(0.0));
+                (frames, last_frame_duration) =
+                    keys::overlay(frames, &keystrokes, last_frame_duration);
+            }
+
+            let frames = output::cap_fps(frames.into_iter(), config.fps_cap).collect();
+
+            (frames, last_frame_duration)
         }
 
         // Discrete selections: keep every resolved position, with no visual
         // dedupe or FPS capping, spaced by a fixed per-frame duration.
         selection::SelectionPlan::Positions(positions) => {
-            let frames = frames::at_positions(&events, terminal_size, positions);
-            output::adjust_discrete_timestamps(frames, config.last_frame_duration).collect()
+            let frames =
+                frames::at_positions(&events, terminal_size, positions.clone()).map(cursor_options);
+            let mut frames: Vec<frames::Frame> =
+                output::adjust_discrete_timestamps(frames, config.last_frame_duration).collect();
+
+            if config.keystrokes.is_some() {
+                let keystrokes = keys::Keystrokes::from_events(&events, 0.0);
+
+                for (frame, time) in frames.iter_mut().zip(positions) {
+                    frame.keys = keystrokes.caption(*time);
+                }
+            }
+
+            (frames, config.last_frame_duration)
+        }
+    };
+
+    // Chapters cover a single forward play.
+    let cues = match &plan {
+        selection::SelectionPlan::Range { start, .. } => {
+            let duration = frames.last().map_or(0.0, |f| f.time) + last_frame_duration;
+            chapters::for_range(summary.markers(), start.unwrap_or(0.0), duration)
+        }
+
+        selection::SelectionPlan::Positions(positions) => {
+            chapters::for_positions(summary.markers(), positions, config.last_frame_duration)
         }
     };
 
+    // The last frame of a single forward play, for a still PNG image next to
+    // animated outputs.
+    let last_index = frames.len().saturating_sub(1);
+
+    let (frames, last_frame_duration) = if config.ping_pong && config.contact_sheet.is_none() {
+        output::ping_pong(frames, last_frame_duration)
+    } else {
+        (frames, last_frame_duration)
+    };
+
+    let last_frame_duration = last_frame_duration + config.loop_pause;
     let count = frames.len() as u64;
+    report.frame_count = count;
+    report.duration = frames.last().map_or(0.0, |f| f.time) + last_frame_duration;
+    let png_sequence = config.contact_sheet.is_none() && !config.full_history && count > 1;
+    let png_file = |output: &Output| {
+        output.format == Format::Png
+            && output.path.extension().is_some_and(|ext| ext == "png")
+            && !is_dir_path(&output.path)
+    };
+
+    // Next to animated outputs, a PNG file gets a still of the last frame.
+    let png_still = png_sequence && outputs.iter().any(|o| o.format.is_animated());
+
+    for output in outputs {
+        if png_sequence && !png_still && png_file(output) {
+            bail!(
+                "{count} frames selected, PNG output of more than one frame needs a directory path (use .apng for an animated PNG)"
+            );
+        }
+    }
+
+    // Transcripts, frame dumps and chapters are written from terminal text and
+    // markers, with no fonts involved.
+    for output in outputs {
+        match output.format {
+            Format::Txt | Format::Ansi => {
+                let encoder = encoder::transcript(output.format == Format::Ansi);
+
+                let mut snapshots = frames
+                    .iter()
+                    .map(|frame| (frame.snapshot.clone(), frame.time));
+
+                write_file(&output.path, |file| encoder.encode(&mut snapshots, file))?;
+            }
+
+            Format::Json => {
+                let encoder =
+                    encoder::frame_dump(canvas_size.0, canvas_size.1, last_frame_duration);
+
+                let mut snapshots = frames
+                    .iter()
+                    .map(|frame| (frame.snapshot.clone(), frame.time));
+
+                write_file(&output.path, |file| encoder.encode(&mut snapshots, file))?;
+            }
+
+            Format::Vtt | Format::Srt => {
+                if cues.is_empty() {
+                    report.warn("no markers in the selection, writing empty chapters".to_owned());
+                }
+
+                write_file(&output.path, |file| match output.format {
+                    Format::Vtt => chapters::write_vtt(file, &cues),
+                    _ => chapters::write_srt(file, &cues),
+                })?;
+            }
+
+            _ => {}
+        }
+    }
+
+    let svg_outputs: Vec<&Output> = outputs.iter().filter(|o| o.format == Format::Svg).collect();
+
+    // With the full history, the PNG outputs take a single tall image instead of
+    // the frames.
+    let raster_outputs: Vec<&Output> = outputs
+        .iter()
+        .filter(|_| history.is_none())
+        .filter(|o| {
+            !matches!(
+                o.format,
+                Format::Svg
+                    | Format::Cast
+                    | Format::Txt
+                    | Format::Ansi
+                    | Format::Json
+                    | Format::Vtt
+                    | Format::Srt
+            )
+        })
+        .collect();
+
+    if svg_outputs.is_empty() && raster_outputs.is_empty() && poster.is_none() && history.is_none()
+    {
+        return Ok(());
+    }
 
     info!(
         "recording terminal size: {}x{}",
         terminal_size.0, terminal_size.1
     );
 
+    if canvas_size != terminal_size {
+        info!(
+            "recording resizes the terminal, canvas size: {}x{}",
+            canvas_size.0, canvas_size.1
+        );
+    }
+
     let font_options = fonts::Options {
         text_font_family: &config.text_font_family,
         emoji_font_family: &config.emoji_font_family,
@@ -217,98 +769,297 @@ pub fn run<I: BufRead, O: Write + Send>(input: I, output: O, config: Config) ->
 
     info!("usable font families: {:?}", fonts.families);
     info!("primary text font family: {}", fonts.text_family);
+    report.font_families = fonts.families.clone();
+    report.text_font_family = Some(fonts.text_family.clone());
+    report.text_font_monospaced = Some(fonts.text_family_monospaced);
 
     if config.renderer == Renderer::Swash && !fonts.colrv1_families.is_empty() {
-        warn!(
+        report.warn(format!(
             "selected font families {:?} contain COLRv1 color glyphs, which the swash renderer does not support yet; glyph fallback will be attempted, or try --renderer resvg",
             fonts.colrv1_families
-        );
+        ));
     }
 
     if config.renderer != Renderer::Swash && config.font_aa_levels != DEFAULT_FONT_AA_LEVELS {
-        warn!("--font-aa-levels only affects the swash renderer");
+        report.warn("--font-aa-levels only affects the swash renderer".to_owned());
     }
 
     if config.renderer != Renderer::Swash && !config.font_hinting {
-        warn!("--hinting only affects the swash renderer");
+        report.warn("--hinting only affects the swash renderer".to_owned());
     }
 
     if !fonts.text_family_monospaced {
-        warn!(
+        report.warn(format!(
             "first font family {:?} is not monospaced; terminal cell metrics may be incorrect",
             fonts.text_family
-        );
+        ));
     }
 
-    let theme_opt = config
-        .theme
-        .or_else(|| header.term_theme.map(Theme::Embedded))
-        .unwrap_or(Theme::Dracula);
-
+    let theme_opt = theme_opt.unwrap_or(Theme::Dracula);
     info!("selected theme: {}", theme_opt);
+    report.theme = Some(theme_opt.to_string());
+    let theme: theme::Theme = theme_opt.try_into()?;
 
-    let settings = renderer::Settings {
-        terminal_size,
-        font_db: fonts.db,
-        font_families: fonts.families,
-        text_family: fonts.text_family,
+    // Every renderer gets its own copy of the font database.
+    let renderer_settings = || renderer::Settings {
+        terminal_size: canvas_size,
+        font_db: fonts.db.clone(),
+        font_families: fonts.families.clone(),
+        text_family: fonts.text_family.clone(),
         font_aa_levels: config.font_aa_levels,
         font_size: config.font_size,
         line_height: config.line_height,
-        theme: theme_opt.try_into()?,
+        theme: theme.clone(),
         bold_is_bright: config.bold_is_bright,
         hinting: config.font_hinting,
+        cursor_color: config.cursor_color,
+        blink_as_bold: config.text_blink == TextBlink::Bold,
     };
 
-    let mut renderer: Box<dyn renderer::Renderer> = match config.renderer {
-        Renderer::Swash => Box::new(renderer::swash(settings)),
-        Renderer::Resvg => Box::new(renderer::resvg(settings)),
-    };
+    if !svg_outputs.is_empty() && config.renderer != Renderer::Resvg {
+        info!("SVG output is laid out by the resvg renderer");
+    }
 
-    let (width, height) = renderer.pixel_size();
+    if !svg_outputs.is_empty() && config.keystrokes.is_some() {
+        report.warn("the keystroke overlay is not drawn on SVG output".to_owned());
+    }
+
+    if !has_format(&[Format::Webp]) && config.webp_quality.is_some() {
+        report.warn("--webp-quality only affects WebP output".to_owned());
+    }
+
+    if !has_format(&[Format::Gif]) && config.gif_quality != GifPreset::default().into() {
+        report.warn("GIF quality options only affect GIF output".to_owned());
+    }
 
-    info!("gif dimensions: {}x{}", width, height);
+    if !has_format(&[Format::Gif]) && config.max_size.is_some() {
+        report.warn("--max-size only affects GIF output".to_owned());
+    }
 
-    let repeat = if config.no_loop {
-        gifski::Repeat::Finite(0)
+    #[allow(deprecated)]
+    let loop_count = if config.no_loop {
+        Some(1)
     } else {
-        gifski::Repeat::Infinite
+        config.loop_count
     };
 
-    let settings = gifski::Settings {
-        width: Some(width as u32),
-        height: Some(height as u32),
-        fast: true,
-        repeat,
-        ..Default::default()
-    };
+    // With several encoders running at once, only the first shows progress.
+    let encoder_settings =
+        |(width, height): (usize, usize), show_progress_bar: bool| encoder::Settings {
+            width,
+            height,
+            frame_count: count,
+            last_frame_duration,
+            loop_count,
+            show_progress_bar: config.show_progress_bar && show_progress_bar,
+        };
 
-    let (collector, writer) = gifski::new(settings)?;
     let start_time = Instant::now();
 
-    thread::scope(|s| {
-        let writer_handle = s.spawn(move || {
-            if config.show_progress_bar {
-                let mut pr = gifski::progress::ProgressBar::new(count);
-                let result = writer.write(output, &mut pr);
-                pr.finish();
-                println!();
-                result
-            } else {
-                let mut pr = gifski::progress::NoProgress {};
-                writer.write(output, &mut pr)
+    for output in &svg_outputs {
+        // SVG frames are written as markup, skipping rasterization.
+        let renderer = renderer::resvg(renderer_settings());
+        info!("output dimensions: {:?}", renderer.pixel_size());
+        report.pixel_size = Some(renderer.pixel_size().into());
+        let started = Instant::now();
+
+        let encoder = encoder::svg(encoder_settings(renderer.pixel_size(), true), renderer);
+
+        let mut snapshots = frames
+            .iter()
+            .map(|frame| (frame.snapshot.clone(), frame.time));
+
+        write_file(&output.path, |file| encoder.encode(&mut snapshots, file))?;
+        report.add_time(Duration::ZERO, started.elapsed());
+    }
+
+    if let Some(snapshot) = &history {
+        // The canvas grows to fit every line.
+        let settings = || renderer::Settings {
+            terminal_size: (canvas_size.0, snapshot.lines.len()),
+            ..renderer_settings()
+        };
+
+        let mut renderer: Box<dyn renderer::Renderer> = match config.renderer {
+            Renderer::Swash => Box::new(renderer::swash(settings())),
+            Renderer::Resvg => Box::new(renderer::resvg(settings())),
+        };
+
+        info!("history: {} lines", snapshot.lines.len());
+        report.pixel_size = Some(renderer.pixel_size().into());
+        let started = Instant::now();
+        let image = renderer.render(snapshot);
+        let render_time = started.elapsed();
+
+        for output in outputs {
+            let encoder: Box<dyn encoder::Encoder> = Box::new(encoder::png());
+            let image = image.clone();
+
+            write_file(&output.path, |file| {
+                encoder.encode(&mut std::iter::once((image, 0.0)), file)
+            })?;
+        }
+
+        report.add_time(render_time, started.elapsed());
+    }
+
+    if !raster_outputs.is_empty() || poster.is_some() {
+        let mut renderer: Box<dyn renderer::Renderer> = match config.renderer {
+            Renderer::Swash => Box::new(renderer::swash(renderer_settings())),
+            Renderer::Resvg => Box::new(renderer::resvg(renderer_settings())),
+        };
+
+        let pixel_size = renderer.pixel_size();
+        info!("output dimensions: {}x{}", pixel_size.0, pixel_size.1);
+        report.pixel_size = Some(pixel_size.into());
+        let started = Instant::now();
+        let mut render_time = Duration::ZERO;
+
+        let mut overlay = config
+            .keystrokes
+            .map(|corner| keys::Overlay::new(corner, canvas_size));
+
+        if let Some((path, time)) = poster {
+            let mut frame = frames::at_positions(&events, terminal_size, vec![time])
+                .map(cursor_options)
+                .next()
+                .expect("a frame for every position");
+
+            if overlay.is_some() {
+                frame.keys = keys::Keystrokes::from_events(&events, 0.0).caption(time);
             }
+
+            let rendered = Instant::now();
+            let image = render_frame(renderer.as_mut(), &frame, overlay.as_mut());
+            render_time += rendered.elapsed();
+            let encoder: Box<dyn encoder::Encoder> = Box::new(encoder::png());
+
+            write_file(path, |file| {
+                encoder.encode(&mut std::iter::once((image, 0.0)), file)
+            })?;
+        }
+
+        // GIFs with a size limit are encoded from images kept in memory, once
+        // the rest are done.
+        let (fitted_outputs, raster_outputs): (Vec<&Output>, Vec<&Output>) = raster_outputs
+            .into_iter()
+            .partition(|o| o.format == Format::Gif && config.max_size.is_some());
+
+        // The caller's writer takes the place of the first GIF output's file.
+        let mut sink = |output: &Output, path: PathBuf| match writer
+            .take_if(|_| output.format == Format::Gif)
+        {
+            Some(writer) => Sink::Writer(writer),
+            None => Sink::File(path),
+        };
+
+        let mut encoders: Vec<(Sink, Box<dyn encoder::Encoder>)> = Vec::new();
+
+        for (i, output) in raster_outputs.into_iter().enumerate() {
+            let settings = encoder_settings(pixel_size, i == 0);
+            let mut path = output.path.clone();
+
+            let encoder: Box<dyn encoder::Encoder> = match output.format {
+                Format::Png if config.contact_sheet.is_some() => {
+                    let captions = captions
+                        .iter()
+                        .map(|text| render_caption(renderer.as_mut(), canvas_size, text))
+                        .collect();
+
+                    let columns = config
+                        .contact_sheet
+                        .as_ref()
+                        .and_then(|sheet| sheet.columns);
+
+                    Box::new(encoder::contact_sheet(settings, columns, captions))
+                }
+                Format::Gif => {
+                    let settings = encoder::Settings {
+                        width: budget::scaled(settings.width, config.gif_scale),
+                        height: budget::scaled(settings.height, config.gif_scale),
+                        ..settings
+                    };
+
+                    Box::new(encoder::gif(settings, config.gif_quality))
+                }
+                Format::Webp => Box::new(encoder::webp(settings, config.webp_quality)),
+                Format::Apng => Box::new(encoder::apng(settings)),
+                Format::Html => Box::new(encoder::html(settings, cues.clone())),
+                Format::Png if png_still && png_file(output) => {
+                    Box::new(encoder::png_still(last_index))
+                }
+                Format::Png if png_sequence || is_dir_path(&output.path) => {
+                    fs::create_dir_all(&output.path)?;
+                    path = output.path.join("manifest.json");
+
+                    Box::new(encoder::png_sequence(settings, output.path.clone()))
+                }
+                Format::Png => Box::new(encoder::png()),
+                #[cfg(feature = "video")]
+                Format::Webm => Box::new(encoder::webm(settings, config.fps_cap)),
+                #[cfg(not(feature = "video"))]
+                Format::Webm => unreachable!("WebM output is not built in"),
+                Format::Svg
+                | Format::Cast
+                | Format::Txt
+                | Format::Ansi
+                | Format::Json
+                | Format::Vtt
+                | Format::Srt => {
+                    unreachable!("not a raster format")
+                }
+            };
+
+            encoders.push((sink(output, path), encoder));
+        }
+
+        let mut images = frames.iter().map(|frame| {
+            let rendered = Instant::now();
+            let image = render_frame(renderer.as_mut(), frame, overlay.as_mut());
+            render_time += rendered.elapsed();
+
+            (image, frame.time)
         });
 
-        for (i, frame) in frames.into_iter().enumerate() {
-            let image = renderer.render(&frame.snapshot);
-            collector.add_frame_rgba(i, image, frame.time + config.last_frame_duration)?;
+        if let (Some(max_size), false) = (config.max_size, fitted_outputs.is_empty()) {
+            let images: Vec<_> = images.collect();
+
+            if !encoders.is_empty() {
+                encode_all(&mut images.iter().cloned(), encoders)?;
+            }
+
+            let settings = encoder_settings(pixel_size, false);
+
+            let (gif, attempt) = budget::fit(
+                &images,
+                &settings,
+                config.gif_quality,
+                config.fps_cap,
+                config.gif_scale,
+                max_size,
+            )?;
+
+            let message = format!(
+                "fitted the GIF into {} with {attempt}",
+                budget::format_size(gif.len() as u64)
+            );
+
+            if attempt == budget::attempts(config.gif_quality, config.fps_cap, config.gif_scale)[0]
+            {
+                info!("{message}");
+            } else {
+                report.warn(message);
+            }
+
+            for output in fitted_outputs {
+                sink(output, output.path.clone()).write(|file| Ok(file.write_all(&gif)?))?;
+            }
+        } else {
+            encode_all(&mut images, encoders)?;
         }
 
-        drop(collector);
-        writer_handle.join().unwrap()?;
-        Result::<()>::Ok(())
-    })?;
+        report.add_time(render_time, started.elapsed());
+    }
 
     info!(
         "rendering finished in {}s",
@@ -317,3 +1068,268 @@ pub fn run<I: BufRead, O: Write + Send>(input: I, output: O, config: Config) ->
 
     Ok(())
 }
+
+/// Feed `images` to every one of `encoders`, each writing to its own sink. With
+/// more than one encoder, each runs on its own thread and gets a copy of every
+/// image.
+fn encode_all(
+    images: &mut dyn Iterator<Item = (ImgVec<RGBA8>, f64)>,
+    encoders: Vec<(Sink, Box<dyn encoder::Encoder>)>,
+) -> Result<()> {
+    if let [_] = encoders.as_slice() {
+        let (sink, encoder) = encoders.into_iter().next().unwrap();
+
+        return sink.write(|output| encoder.encode(images, output));
+    }
+
+    thread::scope(|s| {
+        let mut senders = Vec::new();
+        let mut handles = Vec::new();
+
+        for (sink, encoder) in encoders {
+            let (sender, receiver) = mpsc::sync_channel(ENCODER_QUEUE_LEN);
+            senders.push(sender);
+
+            handles.push(
+                s.spawn(move || sink.write(|output| encoder.encode(&mut receiver.iter(), output))),
+            );
+        }
+
+        for (image, time) in images {
+            // An encoder that failed has dropped its receiver. Its error is
+            // returned once the others are done.
+            senders.retain(|sender| sender.send((image.clone(), time)).is_ok());
+
+            if senders.is_empty() {
+                break;
+            }
+        }
+
+        drop(senders);
+
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().unwrap())
+    })
+}
+
+/// Render `frame`, with its keystroke caption drawn over it by `overlay`.
+fn render_frame(
+    renderer: &mut dyn renderer::Renderer,
+    frame: &frames::Frame,
+    overlay: Option<&mut keys::Overlay>,
+) -> ImgVec<RGBA8> {
+    let mut image = renderer.render(&frame.snapshot);
+
+    if let (Some(overlay), Some(caption)) = (overlay, &frame.keys) {
+        overlay.draw(&mut image, caption, renderer);
+    }
+
+    image
+}
+
+/// Caption text for a contact sheet frame at `time`: the timestamp, followed by
+/// the labels of markers at that time.
+fn caption(time: f64, markers: &[(f64, String)]) -> String {
+    let labels: Vec<String> = markers
+        .iter()
+        .filter(|(t, label)| *t == time && !label.is_empty())
+        .map(|(_, label)| label.chars().filter(|c| !c.is_control()).collect())
+        .collect();
+
+    if labels.is_empty() {
+        format!("{time:.1}s")
+    } else {
+        format!("{time:.1}s  \x1b[1m{}\x1b[22m", labels.join(", "))
+    }
+}
+
+/// Render `text` as the first terminal line, and crop the image to it and its
+/// share of the vertical margins. Text past the last column is cut off.
+fn render_caption(
+    renderer: &mut dyn renderer::Renderer,
+    terminal_size: (usize, usize),
+    text: &str,
+) -> ImgVec<RGBA8> {
+    let mut vt = terminal::build(terminal_size);
+    terminal::feed_str(&mut vt, "\x1b[?7l");
+    terminal::feed_str(&mut vt, text);
+
+    let mut snapshot = terminal::Snapshot::new(&vt);
+    snapshot.cursor = None;
+
+    let image = renderer.render(&snapshot);
+    let (width, height) = renderer.pixel_size();
+
+    // Renderers pad the terminal with half a row above and below.
+    let caption_height = (2 * height / (terminal_size.1 + 1)).min(height);
+    let (buf, width, height) = image
+        .sub_image(0, 0, width, caption_height)
+        .to_contiguous_buf();
+
+    ImgVec::new(buf.into_owned(), width, height)
+}
+
+/// Create the file at `path` and fill it with `write`, removing the file when
+/// writing fails. The write error is returned even if the removal fails too.
+fn write_file(path: &Path, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let mut file = File::create(path)?;
+
+    match write(&mut file) {
+        Ok(ok) => Ok(ok),
+        Err(err) => {
+            let _ = fs::remove_file(path);
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAST: &str = concat!(
+        "{\"version\": 2, \"width\": 8, \"height\": 2}\n",
+        "[0.5, \"o\", \"a\"]\n",
+        "[1.0, \"o\", \"b\"]\n",
+        "[2.0, \"o\", \"c\"]\n",
+    );
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("agg-run-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn config() -> Config {
+        Config {
+            show_progress_bar: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn write_file_returns_the_write_error() {
+        let dir = temp_dir("write-error");
+        let path = dir.join("out.gif");
+
+        let err = write_file(&path, |_| {
+            // The cleanup fails as well once the directory is gone.
+            fs::remove_dir_all(&dir)?;
+            bail!("encoding failed")
+        })
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "encoding failed");
+    }
+
+    #[test]
+    fn writes_a_single_frame_into_a_directory_path() {
+        let dir = temp_dir("single-frame");
+        let shots = dir.join("shots/");
+
+        let outputs = [Output {
+            path: shots.clone(),
+            format: Format::from_path(shots.to_str().unwrap()).unwrap(),
+        }];
+
+        let config = Config {
+            selection: "1".parse().unwrap(),
+            ..config()
+        };
+
+        run_outputs(CAST.as_bytes(), &outputs, config).unwrap();
+
+        assert!(shots.join("frame-0001.png").is_file());
+        assert!(shots.join("manifest.json").is_file());
+        assert!(!shots.join("frame-0002.png").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_run_writes_a_gif_to_the_writer() {
+        let mut output = Vec::new();
+
+        run(CAST.as_bytes(), &mut output, config()).unwrap();
+
+        assert!(output.starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_run_writes_a_fitted_gif_to_the_writer() {
+        let mut output = Vec::new();
+
+        let config = Config {
+            max_size: Some(1_000_000),
+            ..config()
+        };
+
+        run(CAST.as_bytes(), &mut output, config).unwrap();
+
+        assert!(output.starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_no_loop_plays_once() {
+        let looped = |config| {
+            let mut output = Vec::new();
+            run(CAST.as_bytes(), &mut output, config).unwrap();
+
+            output.windows(11).any(|w| w == b"NETSCAPE2.0")
+        };
+
+        assert!(looped(config()));
+
+        assert!(!looped(Config {
+            no_loop: true,
+            ..config()
+        }));
+    }
+
+    #[test]
+    fn writes_a_still_png_next_to_animated_outputs() {
+        let dir = temp_dir("still");
+
+        let outputs = ["demo.gif", "demo.webp", "poster.png"].map(|name| {
+            let path = dir.join(name);
+
+            Output {
+                format: Format::from_path(path.to_str().unwrap()).unwrap(),
+                path,
+            }
+        });
+
+        run_outputs(CAST.as_bytes(), &outputs, config()).unwrap();
+
+        assert!(fs::read(dir.join("demo.gif"))
+            .unwrap()
+            .starts_with(b"GIF89a"));
+        assert_eq!(&fs::read(dir.join("demo.webp")).unwrap()[8..12], b"WEBP");
+        assert!(fs::read(dir.join("poster.png"))
+            .unwrap()
+            .starts_with(b"\x89PNG"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn png_file_alone_needs_a_single_frame() {
+        let dir = temp_dir("png-alone");
+        let path = dir.join("out.png");
+
+        let outputs = [Output {
+            path,
+            format: Format::Png,
+        }];
+
+        assert!(run_outputs(CAST.as_bytes(), &outputs, config()).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
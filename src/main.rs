@@ -1,5 +1,6 @@
 use std::io;
 use std::num::{ParseFloatError, ParseIntError};
+use std::path::{Path, PathBuf};
 use std::{fs::File, io::BufReader, iter};
 
 use anyhow::{anyhow, Result};
@@ -68,6 +69,12 @@ impl clap::builder::TypedValueParser for SelectValueParser {
     }
 }
 
+/// Formats for --dump-frames.
+#[derive(Clone, Copy, ValueEnum)]
+enum FrameDump {
+    Json,
+}
+
 const SELECT_LONG_HELP: &str = "\
 Select frames to render.
 
@@ -81,6 +88,7 @@ POS may be:
   50%                             percent of adjusted duration
   marker:build, marker:3          marker label prefix or 0-based marker index
   event:100                       0-based event index
+  last                            end of the recording
 
 Times are on the adjusted output timeline, after --idle-time-limit and --speed.
 The `markers` selector is standalone; use `marker:<label>` in ranges or lists.
@@ -125,14 +133,134 @@ fn parse_font_aa_levels(s: &str) -> Result<u16, String> {
     Ok(v)
 }
 
+fn parse_webp_quality(s: &str) -> Result<f32, String> {
+    let v: f32 = s.parse().map_err(|e: ParseFloatError| e.to_string())?;
+
+    if !(0.0..=100.0).contains(&v) {
+        return Err(format!("must be 0..=100 (got {v})"));
+    }
+
+    Ok(v)
+}
+
+fn parse_gif_scale(s: &str) -> Result<f64, String> {
+    let v: f64 = s.parse().map_err(|e: ParseFloatError| e.to_string())?;
+
+    if !(v > 0.0 && v <= 1.0) {
+        return Err(format!("must be > 0 and <= 1 (got {v})"));
+    }
+
+    Ok(v)
+}
+
+/// Parse a hex color triplet such as `ff8800` or `#ff8800`.
+fn parse_color(s: &str) -> Result<rgb::RGB8, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("must be a hex triplet such as ff8800 (got {s})"));
+    }
+
+    let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+
+    Ok(rgb::RGB8::new(component(0), component(2), component(4)))
+}
+
+/// Parse a size in bytes with an optional decimal (kB, MB) or binary (KiB,
+/// MiB) unit, e.g. `2MB` or `1.5 MiB`.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|e: ParseFloatError| e.to_string())?;
+
+    let multiplier = match unit.to_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" => 1e3,
+        "m" | "mb" => 1e6,
+        "g" | "gb" => 1e9,
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        "gib" => 1024.0 * 1024.0 * 1024.0,
+        _ => {
+            return Err(format!(
+                "unknown unit {unit:?}, use B, kB, MB, GB, KiB, MiB or GiB"
+            ))
+        }
+    };
+
+    let bytes = (number * multiplier).floor();
+
+    if bytes.is_nan() || bytes < 1.0 {
+        return Err(format!("must be at least 1 byte (got {s})"));
+    }
+
+    Ok(bytes as u64)
+}
+
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
     /// asciicast path/filename or URL
     input_filename_or_url: String,
 
-    /// GIF path/filename
-    output_filename: String,
+    /// Output path/filename, or directory for PNG frames
+    #[clap(required_unless_present = "outputs")]
+    output_filename: Option<String>,
+
+    /// Additional output path, repeatable; each output takes its format from
+    /// its file extension, and all are written from a single rendering pass
+    #[clap(short = 'o', long = "output", value_name = "PATH")]
+    outputs: Vec<String>,
+
+    /// Select output format [default: guessed from output filename, else gif]
+    #[clap(long, value_enum)]
+    format: Option<agg::Format>,
+
+    /// Write the cells, pen attributes and cursor of every output frame instead
+    /// of rendering them; same as --format json
+    #[clap(long, value_enum, value_name = "FORMAT", conflicts_with = "format")]
+    dump_frames: Option<FrameDump>,
+
+    /// Encode WebP lossily at the given quality (0-100) instead of losslessly
+    #[clap(long, value_name = "QUALITY", value_parser = parse_webp_quality)]
+    webp_quality: Option<f32>,
+
+    /// Apply a named set of GIF encoder settings, which the options below override
+    #[clap(long, value_enum, default_value_t = agg::GifPreset::default())]
+    gif_preset: agg::GifPreset,
+
+    /// Set GIF palette quantization quality (1-100)
+    #[clap(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(1..=100))]
+    gif_quality: Option<u8>,
+
+    /// Set GIF lossy compression quality (1-100, 100 is lossless)
+    #[clap(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(1..=100))]
+    gif_lossy_quality: Option<u8>,
+
+    /// Set GIF quality of moving areas (1-100)
+    #[clap(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(1..=100))]
+    gif_motion_quality: Option<u8>,
+
+    /// Scale GIF output down by a factor (0-1), e.g. 0.5 for half size
+    #[clap(long, value_name = "FACTOR", default_value_t = agg::DEFAULT_GIF_SCALE, value_parser = parse_gif_scale)]
+    gif_scale: f64,
+
+    /// Fit GIF output into a size limit, e.g. 2MB, by lowering quality, then
+    /// FPS, then scaling down
+    #[clap(long, value_name = "SIZE", value_parser = parse_size)]
+    max_size: Option<u64>,
+
+    /// Use gifski's fast mode
+    #[clap(long, conflicts_with = "gif_slow")]
+    gif_fast: bool,
+
+    /// Use gifski's slow, higher quality mode
+    #[clap(long)]
+    gif_slow: bool,
 
     /// Specify regular text font families
     #[clap(long, default_value_t = String::from(agg::DEFAULT_TEXT_FONT_FAMILY), conflicts_with = "font_family")]
@@ -185,10 +313,50 @@ struct Cli {
     #[clap(long, default_value_t = agg::DEFAULT_SPEED)]
     speed: f64,
 
-    /// Disable animation loop
+    /// Disable animation loop; same as --loop 1
     #[clap(long)]
     no_loop: bool,
 
+    /// Play the animation N times [default: loop forever]
+    #[clap(long = "loop", value_name = "N", conflicts_with = "no_loop", value_parser = clap::value_parser!(u16).range(1..))]
+    loop_count: Option<u16>,
+
+    /// Play the animation forward, then backward
+    #[clap(long)]
+    ping_pong: bool,
+
+    /// Pause on the last frame before the animation restarts, in seconds, on top of --last-frame-duration
+    #[clap(long, value_name = "SECS", default_value_t = agg::DEFAULT_LOOP_PAUSE)]
+    loop_pause: f64,
+
+    /// Blink the cursor while the terminal is idle, unless the recording asks for a steady cursor
+    #[clap(long)]
+    cursor_blink: bool,
+
+    /// Draw the cursor in this shape, whatever the recording selects
+    #[clap(long, value_enum, value_name = "SHAPE")]
+    cursor_style: Option<agg::CursorShape>,
+
+    /// Draw the cursor in this color (hex triplet) instead of swapping the cell's colors
+    #[clap(long, value_name = "COLOR", value_parser = parse_color)]
+    cursor_color: Option<rgb::RGB8>,
+
+    /// Don't draw the cursor
+    #[clap(long, conflicts_with_all = ["cursor_blink", "cursor_style", "cursor_color"])]
+    hide_cursor: bool,
+
+    /// Draw blinking text (SGR 5/6) static, blinking every half second, or in bold
+    #[clap(long, value_enum, value_name = "MODE", default_value_t = agg::TextBlink::default())]
+    text_blink: agg::TextBlink,
+
+    /// Show recently typed keys from the recording's input events as a fading caption
+    #[clap(long)]
+    keystrokes: bool,
+
+    /// Set the corner of the keystroke caption
+    #[clap(long, value_enum, value_name = "CORNER", requires = "keystrokes", default_value_t = agg::Corner::default())]
+    keystrokes_corner: agg::Corner,
+
     /// Limit idle time to max number of seconds [default: 5]
     #[clap(long)]
     idle_time_limit: Option<f64>,
@@ -201,6 +369,34 @@ struct Cli {
     #[clap(long, default_value_t = agg::DEFAULT_LAST_FRAME_DURATION)]
     last_frame_duration: f64,
 
+    /// Lay the selected frames out in a grid on a single PNG image [default selection: markers]
+    #[clap(long)]
+    contact_sheet: bool,
+
+    /// Set the number of contact sheet columns [default: near-square grid]
+    #[clap(long, value_name = "N", requires = "contact_sheet", value_parser = clap::value_parser!(u16).range(1..))]
+    contact_sheet_columns: Option<u16>,
+
+    /// Caption contact sheet frames with their timestamp and marker label
+    #[clap(long, action = ArgAction::Set, default_value_t = true)]
+    contact_sheet_captions: bool,
+
+    /// Render the final terminal state with all scrollback as one tall PNG image
+    #[clap(long, conflicts_with = "contact_sheet")]
+    full_history: bool,
+
+    /// Also write a PNG image of the given position, e.g. 50%, marker:demo or last (see --select for positions)
+    #[clap(long, value_name = "POS")]
+    poster: Option<agg::TimelinePosition>,
+
+    /// Set the poster image path [default: first output path with a .png extension]
+    #[clap(long, value_name = "PATH", requires = "poster")]
+    poster_path: Option<String>,
+
+    /// Write a JSON report of the run: theme, fonts, sizes, frame count, timings and warnings
+    #[clap(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
     /// Select frames to render (see --help for details)
     #[clap(long, value_name = "SELECTOR", value_parser = SelectValueParser, long_help = SELECT_LONG_HELP)]
     select: Option<agg::SelectionSpec>,
@@ -270,6 +466,19 @@ fn reader(path: &str) -> Result<Box<dyn io::Read>> {
     }
 }
 
+/// The first output's path with a `.png` extension, or `.poster.png` when that
+/// would overwrite an output.
+fn default_poster_path(outputs: &[String]) -> PathBuf {
+    let first = Path::new(&outputs[0]);
+    let path = first.with_extension("png");
+
+    if outputs.iter().any(|output| Path::new(output) == path) {
+        first.with_extension("poster.png")
+    } else {
+        path
+    }
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -288,9 +497,56 @@ fn main() -> Result<()> {
         .format_timestamp(None)
         .init();
 
+    let paths: Vec<String> = cli.output_filename.into_iter().chain(cli.outputs).collect();
+
+    let format = cli.dump_frames.map(|dump| match dump {
+        FrameDump::Json => agg::Format::Json,
+    });
+
+    let format = cli.format.or(format);
+
+    if format.is_some() && paths.len() > 1 {
+        return Err(anyhow!(
+            "--format applies to a single output; with several, each takes its format from the file extension"
+        ));
+    }
+
+    let poster = cli.poster.map(|position| agg::Poster {
+        position,
+        path: cli
+            .poster_path
+            .map(PathBuf::from)
+            .unwrap_or_else(|| default_poster_path(&paths)),
+    });
+
+    let mut gif_quality = agg::GifQuality::from(cli.gif_preset);
+
+    if let Some(quality) = cli.gif_quality {
+        gif_quality.quality = quality;
+    }
+
+    if let Some(quality) = cli.gif_lossy_quality {
+        gif_quality.lossy_quality = quality;
+    }
+
+    if let Some(quality) = cli.gif_motion_quality {
+        gif_quality.motion_quality = quality;
+    }
+
+    if cli.gif_fast || cli.gif_slow {
+        gif_quality.fast = cli.gif_fast;
+    }
+
     let config = agg::Config {
         bold_is_bright: cli.bold_is_bright,
         cols: cli.cols,
+        contact_sheet: cli.contact_sheet.then(|| agg::ContactSheet {
+            columns: cli.contact_sheet_columns.map(usize::from),
+            captions: cli.contact_sheet_captions,
+        }),
+        cursor_blink: cli.cursor_blink,
+        cursor_color: cli.cursor_color,
+        cursor_shape: cli.cursor_style,
         emoji_font_family: cli.emoji_font_family,
         font_dirs: cli.font_dir,
         font_family: cli.font_family,
@@ -298,29 +554,53 @@ fn main() -> Result<()> {
         font_hinting: cli.font_hinting,
         font_size: cli.font_size,
         fps_cap: cli.fps_cap,
+        full_history: cli.full_history,
+        gif_quality,
+        gif_scale: cli.gif_scale,
+        hide_cursor: cli.hide_cursor,
         idle_time_limit: cli.idle_time_limit,
+        keystrokes: cli.keystrokes.then_some(cli.keystrokes_corner),
         last_frame_duration: cli.last_frame_duration,
         line_height: cli.line_height,
-        no_loop: cli.no_loop,
+        poster,
+        max_size: cli.max_size,
+        loop_count: if cli.no_loop { Some(1) } else { cli.loop_count },
+        loop_pause: cli.loop_pause,
+        ping_pong: cli.ping_pong,
         renderer: cli.renderer,
+        report: cli.report,
         rows: cli.rows,
-        selection: cli.select.unwrap_or_default(),
+        selection: cli.select.unwrap_or(if cli.contact_sheet {
+            agg::SelectionSpec::Markers
+        } else {
+            agg::SelectionSpec::default()
+        }),
         speed: cli.speed,
         text_font_family: cli.text_font_family,
+        text_blink: cli.text_blink,
         theme: cli.theme.map(|theme| theme.0),
         show_progress_bar: !cli.quiet,
+        webp_quality: cli.webp_quality,
+        ..Default::default()
     };
 
+    let outputs: Vec<agg::Output> = paths
+        .into_iter()
+        .map(|path| agg::Output {
+            format: format.or_else(|| agg::Format::from_path(&path)).unwrap_or(
+                if cli.contact_sheet || cli.full_history {
+                    agg::Format::Png
+                } else {
+                    agg::Format::default()
+                },
+            ),
+            path: PathBuf::from(path),
+        })
+        .collect();
+
     let input = BufReader::new(reader(&cli.input_filename_or_url)?);
-    let mut output = File::create(&cli.output_filename)?;
 
-    match agg::run(input, &mut output, config) {
-        Ok(ok) => Ok(ok),
-        Err(err) => {
-            std::fs::remove_file(cli.output_filename)?;
-            Err(err)
-        }
-    }
+    agg::run_outputs(input, &outputs, config)
 }
 
 #[cfg(test)]
@@ -377,6 +657,198 @@ mod tests {
         assert_eq!(cli.emoji_font_family, agg::DEFAULT_EMOJI_FONT_FAMILY);
     }
 
+    #[test]
+    fn contact_sheet_columns_require_contact_sheet() {
+        let err = match Cli::try_parse_from([
+            "agg",
+            "--contact-sheet-columns=3",
+            "input.cast",
+            "output.png",
+        ]) {
+            Ok(_) => panic!("expected missing required argument"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn outputs_are_repeatable() {
+        let cli = Cli::try_parse_from([
+            "agg",
+            "input.cast",
+            "-o",
+            "demo.gif",
+            "--output=demo.webp",
+            "-o",
+            "poster.png",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.output_filename, None);
+        assert_eq!(cli.outputs, ["demo.gif", "demo.webp", "poster.png"]);
+    }
+
+    #[test]
+    fn dump_frames_conflicts_with_format() {
+        let err = match Cli::try_parse_from([
+            "agg",
+            "--dump-frames",
+            "json",
+            "--format",
+            "gif",
+            "input.cast",
+            "frames.out",
+        ]) {
+            Ok(_) => panic!("expected --dump-frames/--format conflict"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn keystrokes_corner_requires_keystrokes() {
+        let err = match Cli::try_parse_from([
+            "agg",
+            "--keystrokes-corner",
+            "top-left",
+            "input.cast",
+            "demo.gif",
+        ]) {
+            Ok(_) => panic!("expected --keystrokes to be required"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn parses_cursor_options() {
+        let cli = Cli::try_parse_from([
+            "agg",
+            "--cursor-style",
+            "hollow",
+            "--cursor-color",
+            "#ff8800",
+            "input.cast",
+            "demo.gif",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.cursor_style, Some(agg::CursorShape::Hollow));
+        assert_eq!(cli.cursor_color, Some(rgb::RGB8::new(0xff, 0x88, 0x00)));
+        assert!(parse_color("ff88").is_err());
+        assert!(parse_color("gg8800").is_err());
+    }
+
+    #[test]
+    fn parses_text_blink_mode() {
+        let cli = Cli::try_parse_from(["agg", "input.cast", "demo.gif"]).unwrap();
+        assert_eq!(cli.text_blink, agg::TextBlink::Static);
+
+        let cli = Cli::try_parse_from(["agg", "--text-blink", "animate", "input.cast", "demo.gif"])
+            .unwrap();
+        assert_eq!(cli.text_blink, agg::TextBlink::Animate);
+    }
+
+    #[test]
+    fn hide_cursor_conflicts_with_cursor_style() {
+        let err = match Cli::try_parse_from([
+            "agg",
+            "--hide-cursor",
+            "--cursor-style",
+            "bar",
+            "input.cast",
+            "demo.gif",
+        ]) {
+            Ok(_) => panic!("expected --hide-cursor/--cursor-style conflict"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn output_path_is_required() {
+        let err = match Cli::try_parse_from(["agg", "input.cast"]) {
+            Ok(_) => panic!("expected missing required argument"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn gif_fast_conflicts_with_gif_slow() {
+        let err = match Cli::try_parse_from([
+            "agg",
+            "--gif-fast",
+            "--gif-slow",
+            "input.cast",
+            "output.gif",
+        ]) {
+            Ok(_) => panic!("expected argument conflict"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn gif_quality_rejects_zero() {
+        let err = match Cli::try_parse_from(["agg", "--gif-quality=0", "input.cast", "output.gif"])
+        {
+            Ok(_) => panic!("expected invalid value"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn max_size_accepts_units() {
+        assert_eq!(parse_size("2MB"), Ok(2_000_000));
+        assert_eq!(parse_size("1.5 MiB"), Ok(1_572_864));
+        assert_eq!(parse_size("500k"), Ok(500_000));
+        assert_eq!(parse_size("1234"), Ok(1234));
+        assert!(parse_size("2 parsecs").is_err());
+        assert!(parse_size("0MB").is_err());
+    }
+
+    #[test]
+    fn loop_conflicts_with_no_loop() {
+        let err =
+            match Cli::try_parse_from(["agg", "--loop=3", "--no-loop", "input.cast", "output.gif"])
+            {
+                Ok(_) => panic!("expected argument conflict"),
+                Err(err) => err,
+            };
+
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn poster_path_defaults_next_to_first_output() {
+        assert_eq!(
+            default_poster_path(&["out/demo.gif".to_owned()]),
+            PathBuf::from("out/demo.png")
+        );
+
+        assert_eq!(
+            default_poster_path(&["demo.gif".to_owned(), "demo.png".to_owned()]),
+            PathBuf::from("demo.poster.png")
+        );
+    }
+
+    #[test]
+    fn poster_accepts_last() {
+        let cli =
+            Cli::try_parse_from(["agg", "--poster=last", "input.cast", "output.gif"]).unwrap();
+
+        assert_eq!(cli.poster, Some(agg::TimelinePosition::Last));
+    }
+
     #[test]
     fn font_aa_levels_accepts_level_count() {
         let cli = Cli::try_parse_from(["agg", "--font-antialiasing=4", "input.cast", "output.gif"])
@@ -413,6 +885,31 @@ mod tests {
         assert_eq!(err.kind(), ErrorKind::ValueValidation);
     }
 
+    #[test]
+    fn webp_quality_rejects_out_of_range_value() {
+        let err =
+            match Cli::try_parse_from(["agg", "--webp-quality=101", "input.cast", "output.webp"]) {
+                Ok(_) => panic!("expected validation error"),
+                Err(err) => err,
+            };
+
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn gif_scale_rejects_out_of_range_value() {
+        for scale in ["0", "1.5"] {
+            let arg = format!("--gif-scale={scale}");
+
+            let err = match Cli::try_parse_from(["agg", &arg, "input.cast", "output.gif"]) {
+                Ok(_) => panic!("expected validation error"),
+                Err(err) => err,
+            };
+
+            assert_eq!(err.kind(), ErrorKind::ValueValidation);
+        }
+    }
+
     #[test]
     fn select_accepts_valid_selector() {
         use agg::SelectionSpec;
@@ -2,6 +2,12 @@
 
 use crate::frames::Frame;
 
+/// How long a blinking cursor stays on, and then off.
+const CURSOR_BLINK_INTERVAL: f64 = 0.5;
+
+/// How long blinking text stays visible, and then hidden.
+const TEXT_BLINK_INTERVAL: f64 = 0.5;
+
 /// Drop frames whose terminal state matches the previously emitted frame. Kept
 /// frames keep their original timestamps, so the delay to the next change is
 /// preserved.
@@ -64,6 +70,7 @@ pub fn cap_fps(frames: impl Iterator<Item = Frame>, fps_cap: u8) -> impl Iterato
 
                 Some(w) if frame.time - w.time < max_frame_time => {
                     w.snapshot = frame.snapshot;
+                    w.keys = frame.keys;
                 }
 
                 Some(_) => return Some(window.replace(frame).unwrap()),
@@ -74,6 +81,120 @@ pub fn cap_fps(frames: impl Iterator<Item = Frame>, fps_cap: u8) -> impl Iterato
     })
 }
 
+/// Blink the cursor of frames that show a blinking cursor style, adding frames
+/// that hide and show it again every `CURSOR_BLINK_INTERVAL` until the next
+/// frame. Every frame starts with the cursor on, as a terminal restarts the
+/// blink on output. The last frame blinks for `last_frame_duration`; returns
+/// the frames along with how long the new last frame is shown.
+pub fn blink_cursor(frames: Vec<Frame>, last_frame_duration: f64) -> (Vec<Frame>, f64) {
+    let Some(end) = frames.last().map(|f| f.time + last_frame_duration) else {
+        return (frames, last_frame_duration);
+    };
+
+    let mut out = Vec::with_capacity(frames.len());
+    let mut frames = frames.into_iter().peekable();
+
+    while let Some(frame) = frames.next() {
+        let next_time = frames.peek().map_or(end, |f| f.time);
+        let blinks = frame.snapshot.cursor.is_some() && frame.snapshot.cursor_style.blinking;
+        let cursor = frame.snapshot.cursor;
+        let start = frame.time;
+        out.push(frame);
+
+        if !blinks {
+            continue;
+        }
+
+        for i in 1.. {
+            let time = start + i as f64 * CURSOR_BLINK_INTERVAL;
+
+            if time >= next_time {
+                break;
+            }
+
+            let mut blink = out.last().unwrap().clone();
+            blink.time = time;
+            blink.snapshot.cursor = if i % 2 == 0 { cursor } else { None };
+            out.push(blink);
+        }
+    }
+
+    let last_frame_duration = end - out.last().unwrap().time;
+
+    (out, last_frame_duration)
+}
+
+/// Blink the text of frames that show blinking text, adding frames that hide
+/// and show it every `TEXT_BLINK_INTERVAL` until the next frame. Unlike the
+/// cursor, text blinks in step with the animation clock rather than restarting
+/// on output. The last frame blinks for `last_frame_duration`; returns the
+/// frames along with how long the new last frame is shown.
+pub fn blink_text(frames: Vec<Frame>, last_frame_duration: f64) -> (Vec<Frame>, f64) {
+    let Some(end) = frames.last().map(|f| f.time + last_frame_duration) else {
+        return (frames, last_frame_duration);
+    };
+
+    let phase = |time: f64| (time / TEXT_BLINK_INTERVAL).floor() as u64;
+    let mut out = Vec::with_capacity(frames.len());
+    let mut frames = frames.into_iter().peekable();
+
+    while let Some(mut frame) = frames.next() {
+        let next_time = frames.peek().map_or(end, |f| f.time);
+
+        if !frame.snapshot.has_blinking_text() {
+            out.push(frame);
+            continue;
+        }
+
+        let start = phase(frame.time);
+        frame.snapshot.blink_hidden = start % 2 == 1;
+        out.push(frame);
+
+        for i in start + 1.. {
+            let time = i as f64 * TEXT_BLINK_INTERVAL;
+
+            if time >= next_time {
+                break;
+            }
+
+            let mut blink = out.last().unwrap().clone();
+            blink.time = time;
+            blink.snapshot.blink_hidden = i % 2 == 1;
+            out.push(blink);
+        }
+    }
+
+    let last_frame_duration = end - out.last().unwrap().time;
+
+    (out, last_frame_duration)
+}
+
+/// Append the frames between the last and the first in reverse, so that the
+/// animation plays forward and then backward, each frame shown as long on the
+/// way back as on the way forward. The last frame is shown for
+/// `last_frame_duration` at the turn. Returns the frames along with how long
+/// the new last frame is shown.
+pub fn ping_pong(mut frames: Vec<Frame>, last_frame_duration: f64) -> (Vec<Frame>, f64) {
+    let n = frames.len();
+
+    if n < 3 {
+        return (frames, last_frame_duration);
+    }
+
+    let second_duration = frames[2].time - frames[1].time;
+    let mut time = frames[n - 1].time + last_frame_duration;
+
+    for i in (1..n - 1).rev() {
+        let duration = frames[i + 1].time - frames[i].time;
+        let mut frame = frames[i].clone();
+        frame.time = time;
+        time += duration;
+        frames.push(frame);
+    }
+
+    (frames, second_duration)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,8 +207,13 @@ mod tests {
             time,
             snapshot: Snapshot {
                 lines: Vec::new(),
+                decorations: Vec::new(),
+                graphemes: Vec::new(),
                 cursor: Some((tag, 0)),
+                cursor_style: Default::default(),
+                blink_hidden: false,
             },
+            keys: None,
         }
     }
 
@@ -170,6 +296,69 @@ mod tests {
         assert_eq!(tags(&frames), vec![0, 1, 2]);
     }
 
+    #[test]
+    fn ping_pong_mirrors_frame_durations() {
+        let frames = vec![
+            tagged(0.0, 0),
+            tagged(1.0, 1),
+            tagged(3.0, 2),
+            tagged(6.0, 3),
+        ];
+
+        let (frames, last_frame_duration) = ping_pong(frames, 2.0);
+
+        assert_eq!(times(&frames), vec![0.0, 1.0, 3.0, 6.0, 8.0, 11.0]);
+        assert_eq!(tags(&frames), vec![0, 1, 2, 3, 2, 1]);
+        assert_eq!(last_frame_duration, 2.0);
+    }
+
+    #[test]
+    fn blink_cursor_toggles_cursor_while_idle() {
+        let mut steady = tagged(1.2, 1);
+        steady.snapshot.cursor_style.blinking = false;
+
+        let frames = vec![tagged(0.0, 0), steady, tagged(3.0, 2)];
+        let (frames, last_frame_duration) = blink_cursor(frames, 1.0);
+
+        assert_eq!(times(&frames), vec![0.0, 0.5, 1.0, 1.2, 3.0, 3.5]);
+        assert_eq!(last_frame_duration, 0.5);
+
+        let cursors: Vec<_> = frames.iter().map(|f| f.snapshot.cursor).collect();
+
+        assert_eq!(
+            cursors,
+            vec![
+                Some((0, 0)),
+                None,
+                Some((0, 0)),
+                Some((1, 0)),
+                Some((2, 0)),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn blink_text_toggles_blinking_text_on_the_animation_clock() {
+        let mut vt = avt::Vt::builder().size(4, 1).build();
+        vt.feed_str("\x1b[5mx");
+
+        let blinking = |time: f64| Frame {
+            snapshot: Snapshot::from_vt(&vt),
+            ..tagged(time, 0)
+        };
+
+        let frames = vec![blinking(0.0), tagged(0.7, 1), blinking(1.0)];
+        let (frames, last_frame_duration) = blink_text(frames, 1.0);
+
+        assert_eq!(times(&frames), vec![0.0, 0.5, 0.7, 1.0, 1.5]);
+        assert_eq!(last_frame_duration, 0.5);
+
+        let hidden: Vec<_> = frames.iter().map(|f| f.snapshot.blink_hidden).collect();
+
+        assert_eq!(hidden, vec![false, true, false, false, true]);
+    }
+
     #[test]
     fn discrete_timestamps_are_sequential() {
         let frames = vec![tagged(2.0, 0), tagged(5.0, 1), tagged(10.0, 2)];
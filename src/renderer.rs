@@ -4,9 +4,11 @@ mod swash;
 use imgref::ImgVec;
 use rgb::{RGB8, RGBA8};
 
-use crate::terminal::Snapshot;
+use crate::terminal::{CursorShape, Decoration, Snapshot, UnderlineStyle};
 use crate::theme::Theme;
 
+pub use self::resvg::ResvgRenderer;
+
 pub trait Renderer {
     fn render(&mut self, snapshot: &Snapshot) -> ImgVec<RGBA8>;
     fn pixel_size(&self) -> (usize, usize);
@@ -23,6 +25,10 @@ pub struct Settings {
     pub theme: Theme,
     pub bold_is_bright: bool,
     pub hinting: bool,
+    /// Color of the cursor, instead of the colors of the cell under it.
+    pub cursor_color: Option<RGB8>,
+    /// Draw blinking text bold.
+    pub blink_as_bold: bool,
 }
 
 pub fn resvg<'a>(settings: Settings) -> resvg::ResvgRenderer<'a> {
@@ -40,7 +46,40 @@ struct TextAttrs {
     bold: bool,
     faint: bool,
     italic: bool,
-    underline: bool,
+    underline: Option<UnderlineStyle>,
+    underline_color: Option<avt::Color>,
+    strikethrough: bool,
+    blink: bool,
+    /// Draw the background only.
+    hidden: bool,
+}
+
+impl TextAttrs {
+    /// Add the underline style and color of a cell, which avt's pen doesn't
+    /// have.
+    fn decorate(self, decoration: Decoration) -> Self {
+        TextAttrs {
+            underline: self.underline.map(|_| decoration.underline_style),
+            underline_color: decoration.underline_color,
+            ..self
+        }
+    }
+
+    /// Draw blinking text bold when `as_bold` is set, and hide it with its
+    /// underline and strikethrough in the hidden phase of its blink.
+    fn blink(self, as_bold: bool, hidden: bool) -> Self {
+        if !self.blink {
+            return self;
+        }
+
+        TextAttrs {
+            bold: self.bold || as_bold,
+            underline: self.underline.filter(|_| !hidden),
+            strikethrough: self.strikethrough && !hidden,
+            hidden,
+            ..self
+        }
+    }
 }
 
 fn text_attrs(
@@ -50,10 +89,13 @@ fn text_attrs(
     row: usize,
     theme: &Theme,
     bold_is_bright: bool,
+    cursor_color: Option<RGB8>,
 ) -> TextAttrs {
     let mut foreground = pen.foreground();
     let mut background = pen.background();
-    let inverse = cursor == &Some((col, row));
+    let at_cursor = cursor == &Some((col, row));
+    let cursor_color = cursor_color.filter(|_| at_cursor);
+    let inverse = at_cursor && cursor_color.is_none();
 
     if bold_is_bright && pen.is_bold() {
         if let Some(avt::Color::Indexed(n)) = foreground {
@@ -70,16 +112,33 @@ fn text_attrs(
         background = Some(bg);
     }
 
+    if let Some(color) = cursor_color {
+        foreground = Some(background.unwrap_or(avt::Color::RGB(theme.background)));
+        background = Some(avt::Color::RGB(color));
+    }
+
     TextAttrs {
         foreground,
         background,
         bold: pen.is_bold(),
         faint: pen.is_faint(),
         italic: pen.is_italic(),
-        underline: pen.is_underline(),
+        underline: pen.is_underline().then_some(UnderlineStyle::Single),
+        underline_color: None,
+        strikethrough: pen.is_strikethrough(),
+        blink: pen.is_blink(),
+        hidden: false,
     }
 }
 
+/// The cursor position when the cursor is a block, which is drawn by swapping
+/// the colors of the cell under it. Other shapes are drawn over the text.
+fn block_cursor(snapshot: &Snapshot) -> Option<(usize, usize)> {
+    snapshot
+        .cursor
+        .filter(|_| snapshot.cursor_style.shape == CursorShape::Block)
+}
+
 fn color_to_rgb(c: &avt::Color, theme: &Theme) -> RGB8 {
     match c {
         avt::Color::RGB(c) => *c,
@@ -90,6 +149,7 @@ fn color_to_rgb(c: &avt::Color, theme: &Theme) -> RGB8 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::terminal::CursorStyle;
 
     const COLS: usize = 40;
     const ROWS: usize = 12;
@@ -363,6 +423,133 @@ mod tests {
         assert_rgb_close(cell_center(&image, 6, 5), PALETTE[YELLOW], 0);
     }
 
+    #[test]
+    fn swash_draws_bar_and_underline_cursors_over_the_cell() {
+        let mut renderer = swash(settings(false));
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Bar);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[YELLOW], 0);
+        assert_rgb_close(cell_pixel(&image, 4, 5, 0.0, 0.5), PALETTE[BLUE], 0);
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Underline);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[YELLOW], 0);
+        assert_rgb_close(cell_pixel(&image, 4, 5, 0.5, 0.98), PALETTE[BLUE], 0);
+    }
+
+    #[test]
+    fn swash_draws_hollow_and_colored_cursors() {
+        let mut renderer = swash(settings(false));
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Hollow);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[YELLOW], 0);
+        assert_rgb_close(cell_pixel(&image, 4, 5, 0.0, 0.5), PALETTE[BLUE], 0);
+        assert_rgb_close(cell_pixel(&image, 4, 5, 0.5, 0.98), PALETTE[BLUE], 0);
+
+        let mut renderer = swash(settings_with_cursor_color(PALETTE[RED]));
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Block);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[RED], 0);
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Bar);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[YELLOW], 0);
+        assert_rgb_close(cell_pixel(&image, 4, 5, 0.0, 0.5), PALETTE[RED], 0);
+    }
+
+    #[test]
+    fn resvg_draws_hollow_and_colored_cursors() {
+        let mut renderer = resvg(settings(false));
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Hollow);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[YELLOW], 3);
+        assert_closer_to(
+            cell_pixel(&image, 4, 5, 0.02, 0.5),
+            PALETTE[BLUE],
+            PALETTE[YELLOW],
+        );
+        assert_closer_to(
+            cell_pixel(&image, 4, 5, 0.98, 0.5),
+            PALETTE[BLUE],
+            PALETTE[YELLOW],
+        );
+
+        let mut renderer = resvg(settings_with_cursor_color(PALETTE[RED]));
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Block);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[RED], 3);
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Bar);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[YELLOW], 3);
+        assert_closer_to(
+            cell_pixel(&image, 4, 5, 0.02, 0.5),
+            PALETTE[RED],
+            PALETTE[YELLOW],
+        );
+    }
+
+    #[test]
+    fn swash_draws_strikethrough_and_styled_underlines() {
+        assert_decorations_drawn(&mut swash(settings(false)));
+    }
+
+    #[test]
+    fn resvg_draws_strikethrough_and_styled_underlines() {
+        assert_decorations_drawn(&mut resvg(settings(false)));
+    }
+
+    #[test]
+    fn swash_hides_blinking_text_in_the_hidden_phase() {
+        assert_blink_hides_text(&mut swash(settings(false)));
+    }
+
+    #[test]
+    fn resvg_hides_blinking_text_in_the_hidden_phase() {
+        assert_blink_hides_text(&mut resvg(settings(false)));
+    }
+
+    #[test]
+    fn swash_draws_combining_marks_over_their_base() {
+        assert_combining_mark_drawn(&mut swash(settings(false)));
+    }
+
+    #[test]
+    fn resvg_draws_combining_marks_over_their_base() {
+        assert_combining_mark_drawn(&mut resvg(settings(false)));
+    }
+
+    #[test]
+    fn blink_as_bold_draws_blinking_text_bold() {
+        let mut vt = avt::Vt::builder().size(4, 1).build();
+        vt.feed_str("\x1b[5mx\x1b[25my");
+        let cells = vt.view().next().unwrap().cells();
+        let attrs = |cell: &avt::Cell| text_attrs(cell.pen(), &None, 0, 0, &theme(), false, None);
+
+        assert!(attrs(&cells[0]).blink(true, false).bold);
+        assert!(!attrs(&cells[0]).blink(false, false).bold);
+        assert!(!attrs(&cells[1]).blink(true, true).bold);
+        assert!(!attrs(&cells[1]).blink(true, true).hidden);
+    }
+
+    #[test]
+    fn resvg_draws_bar_and_underline_cursors_over_the_cell() {
+        let mut renderer = resvg(settings(false));
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Bar);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[YELLOW], 3);
+        assert_closer_to(
+            cell_pixel(&image, 4, 5, 0.02, 0.5),
+            PALETTE[BLUE],
+            PALETTE[YELLOW],
+        );
+
+        let image = render_with_cursor_style(&mut renderer, (4, 5), CursorShape::Underline);
+        assert_rgb_close(cell_center(&image, 4, 5), PALETTE[YELLOW], 3);
+        assert_closer_to(
+            cell_pixel(&image, 4, 5, 0.5, 0.98),
+            PALETTE[BLUE],
+            PALETTE[YELLOW],
+        );
+    }
+
     #[test]
     fn swash_renders_mosaic_symbols_crunchy() {
         let mut renderer = swash(settings(false));
@@ -614,6 +801,8 @@ mod tests {
             theme: theme(),
             bold_is_bright: false,
             hinting: true,
+            cursor_color: None,
+            blink_as_bold: false,
         };
 
         let mut renderer = resvg(settings);
@@ -668,6 +857,8 @@ mod tests {
             theme: theme(),
             bold_is_bright: false,
             hinting: true,
+            cursor_color: None,
+            blink_as_bold: false,
         };
 
         let mut renderer = swash(settings);
@@ -697,6 +888,8 @@ mod tests {
             theme: theme(),
             bold_is_bright: false,
             hinting: true,
+            cursor_color: None,
+            blink_as_bold: false,
         };
 
         let mut fallback_renderer = swash(settings(make_db(), vec![]));
@@ -736,7 +929,7 @@ mod tests {
         let off_pixels: Vec<_> = cell_rgb_pixels(&aa_off, 0, 0).collect();
 
         assert!(
-            off_pixels.iter().any(|&p| p == FG),
+            off_pixels.contains(&FG),
             "expected two-level 'm' to retain foreground ink",
         );
 
@@ -795,6 +988,8 @@ mod tests {
             theme: theme(),
             bold_is_bright: false,
             hinting: true,
+            cursor_color: None,
+            blink_as_bold: false,
         };
 
         let mut renderer = swash(settings);
@@ -828,6 +1023,8 @@ mod tests {
             theme: theme(),
             bold_is_bright: false,
             hinting: true,
+            cursor_color: None,
+            blink_as_bold: false,
         };
 
         let mut renderer = swash(settings);
@@ -842,7 +1039,96 @@ mod tests {
         lines: Vec<avt::Line>,
         cursor: Option<(usize, usize)>,
     ) -> ImgVec<RGBA8> {
-        renderer.render(&Snapshot { lines, cursor })
+        renderer.render(&Snapshot {
+            lines,
+            decorations: Vec::new(),
+            graphemes: Vec::new(),
+            cursor,
+            cursor_style: Default::default(),
+            blink_hidden: false,
+        })
+    }
+
+    fn render_decorations<R: Renderer>(renderer: &mut R) -> ImgVec<RGBA8> {
+        let mut terminal = crate::terminal::build((COLS, ROWS));
+
+        crate::terminal::feed_str(
+            &mut terminal,
+            "\x1b[9m   \x1b[0m   \x1b[4:3;58;5;4m   \x1b[4:2m   \x1b[0m\x1b[?25l",
+        );
+
+        renderer.render(&Snapshot::new(&terminal))
+    }
+
+    fn assert_decorations_drawn<R: Renderer>(renderer: &mut R) {
+        let image = render_decorations(renderer);
+        let has_ink = |col, color: RGB8| {
+            cell_rgb_pixels(&image, col, 0).any(|px| rgb_distance(px, color) < rgb_distance(px, BG))
+        };
+
+        assert!(has_ink(1, FG), "expected a strikethrough");
+        assert!(!has_ink(4, FG), "expected a blank cell");
+        assert!(has_ink(7, PALETTE[BLUE]), "expected a curly underline");
+        assert!(
+            !cell_rgb_pixels(&image, 7, 0)
+                .any(|px| rgb_distance(px, FG) < rgb_distance(px, PALETTE[BLUE])),
+            "expected the underline color"
+        );
+        assert!(has_ink(10, PALETTE[BLUE]), "expected a double underline");
+    }
+
+    fn assert_combining_mark_drawn<R: Renderer>(renderer: &mut R) {
+        let mut terminal = crate::terminal::build((COLS, ROWS));
+        crate::terminal::feed_str(&mut terminal, "e e\u{301}|\x1b[?25l");
+
+        let image = renderer.render(&Snapshot::new(&terminal));
+
+        // Ink above the x-height, where only the accent reaches.
+        let ink = |col| {
+            let (x_l, x_r, y_t, y_b) = cell_bounds(&image, col, 0, 0.0, 1.0);
+            let (buf, width) = (image.buf(), image.width());
+
+            (y_t..y_t + (y_b - y_t) / 3)
+                .flat_map(|y| (x_l..x_r).map(move |x| buf[y * width + x]))
+                .filter(|px| rgb_distance(RGB8::new(px.r, px.g, px.b), BG) > 20)
+                .count()
+        };
+
+        assert!(ink(2) > ink(0) + 5, "expected an accent over the second e");
+        assert_cell_has_ink(&image, 3, 0, 1);
+    }
+
+    fn assert_blink_hides_text<R: Renderer>(renderer: &mut R) {
+        let mut vt = avt::Vt::builder().size(COLS, ROWS).build();
+        vt.feed_str("\x1b[5;41mX\x1b[?25l");
+
+        let mut snapshot = Snapshot::from_vt(&vt);
+        let image = renderer.render(&snapshot);
+        assert!(cell_rgb_pixels(&image, 0, 0)
+            .any(|px| rgb_distance(px, FG) < rgb_distance(px, PALETTE[RED])));
+
+        snapshot.blink_hidden = true;
+        let image = renderer.render(&snapshot);
+        assert!(cell_rgb_pixels(&image, 0, 0)
+            .all(|px| rgb_distance(px, PALETTE[RED]) < rgb_distance(px, FG)));
+    }
+
+    fn render_with_cursor_style<R: Renderer>(
+        renderer: &mut R,
+        cursor: (usize, usize),
+        shape: CursorShape,
+    ) -> ImgVec<RGBA8> {
+        renderer.render(&Snapshot {
+            lines: vt_lines(),
+            decorations: Vec::new(),
+            graphemes: Vec::new(),
+            cursor: Some(cursor),
+            cursor_style: CursorStyle {
+                shape,
+                blinking: false,
+            },
+            blink_hidden: false,
+        })
     }
 
     fn settings(bold_is_bright: bool) -> Settings {
@@ -871,6 +1157,8 @@ mod tests {
             theme: theme(),
             bold_is_bright,
             hinting: true,
+            cursor_color: None,
+            blink_as_bold: false,
         }
     }
 
@@ -889,6 +1177,8 @@ mod tests {
             theme: theme(),
             bold_is_bright: false,
             hinting: true,
+            cursor_color: None,
+            blink_as_bold: false,
         }
     }
 
@@ -899,6 +1189,13 @@ mod tests {
         }
     }
 
+    fn settings_with_cursor_color(cursor_color: RGB8) -> Settings {
+        Settings {
+            cursor_color: Some(cursor_color),
+            ..settings(false)
+        }
+    }
+
     fn theme() -> Theme {
         [BG, FG]
             .into_iter()
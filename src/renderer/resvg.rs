@@ -1,10 +1,10 @@
 use std::{fmt::Write, sync::Arc};
 
 use imgref::ImgVec;
-use rgb::{FromSlice, RGBA8};
+use rgb::{FromSlice, RGB8, RGBA8};
 
-use super::{color_to_rgb, text_attrs, Renderer, Settings, TextAttrs};
-use crate::terminal::Snapshot;
+use super::{block_cursor, color_to_rgb, text_attrs, Renderer, Settings, TextAttrs};
+use crate::terminal::{CursorShape, Snapshot, UnderlineStyle};
 use crate::theme::Theme;
 
 pub struct ResvgRenderer<'a> {
@@ -14,10 +14,14 @@ pub struct ResvgRenderer<'a> {
     pixel_height: usize,
     char_width: f64,
     row_height: f64,
+    font_size: f64,
+    line_thickness: f64,
     options: usvg::Options<'a>,
     transform: tiny_skia::Transform,
     header: String,
     bold_is_bright: bool,
+    cursor_color: Option<RGB8>,
+    blink_as_bold: bool,
 }
 
 fn color_to_style(color: &avt::Color, theme: &Theme) -> String {
@@ -37,7 +41,8 @@ fn text_class(attrs: &TextAttrs) -> String {
         classes.push("it");
     }
 
-    if attrs.underline {
+    // Other underlines are drawn as paths.
+    if attrs.underline == Some(UnderlineStyle::Single) && attrs.underline_color.is_none() {
         classes.push("un");
     }
 
@@ -230,10 +235,15 @@ impl<'a> ResvgRenderer<'a> {
             pixel_height,
             char_width,
             row_height,
+            font_size,
+            // About the underline thickness of a typical monospace font.
+            line_thickness: (font_size * 0.06).max(1.0),
             options,
             transform,
             header,
             bold_is_bright: settings.bold_is_bright,
+            cursor_color: settings.cursor_color,
+            blink_as_bold: settings.blink_as_bold,
         }
     }
 
@@ -285,17 +295,20 @@ impl<'a> ResvgRenderer<'a> {
         self.char_width * width as f64
     }
 
-    fn svg_for_frame(&self, lines: &[avt::Line], cursor: Option<(usize, usize)>) -> String {
+    fn svg_for_frame(&self, snapshot: &Snapshot) -> String {
         let mut svg = self.header.clone();
-        self.push_lines(&mut svg, lines, cursor);
+        self.push_lines(&mut svg, snapshot);
         svg.push_str(Self::footer());
 
         svg
     }
 
-    fn push_lines(&self, svg: &mut String, lines: &[avt::Line], cursor: Option<(usize, usize)>) {
-        self.push_background(svg, lines, cursor);
-        self.push_text(svg, lines, cursor);
+    fn push_lines(&self, svg: &mut String, snapshot: &Snapshot) {
+        let cursor = block_cursor(snapshot);
+        self.push_background(svg, &snapshot.lines, cursor);
+        self.push_text(svg, snapshot, cursor);
+        self.push_decorations(svg, snapshot, cursor);
+        self.push_cursor(svg, snapshot);
     }
 
     fn push_background(
@@ -320,6 +333,7 @@ impl<'a> ResvgRenderer<'a> {
                     row,
                     &self.theme,
                     self.bold_is_bright,
+                    self.cursor_color,
                 );
 
                 if attrs.background.is_none() {
@@ -345,10 +359,10 @@ impl<'a> ResvgRenderer<'a> {
         svg.push_str("</g>");
     }
 
-    fn push_text(&self, svg: &mut String, lines: &[avt::Line], cursor: Option<(usize, usize)>) {
+    fn push_text(&self, svg: &mut String, snapshot: &Snapshot, cursor: Option<(usize, usize)>) {
         svg.push_str(r#"<text class="default-text-fill">"#);
 
-        for (row, line) in lines.iter().enumerate() {
+        for (row, line) in snapshot.lines.iter().enumerate() {
             let y = self.y_pct(row);
             let mut did_dy = false;
 
@@ -365,7 +379,22 @@ impl<'a> ResvgRenderer<'a> {
                     continue;
                 }
 
-                let attrs = text_attrs(pen, &cursor, col, row, &self.theme, self.bold_is_bright);
+                let attrs = text_attrs(
+                    pen,
+                    &cursor,
+                    col,
+                    row,
+                    &self.theme,
+                    self.bold_is_bright,
+                    self.cursor_color,
+                )
+                .decorate(snapshot.decoration(col, row))
+                .blink(self.blink_as_bold, snapshot.blink_hidden);
+
+                if attrs.hidden {
+                    col += cell_width;
+                    continue;
+                }
 
                 svg.push_str("<tspan ");
 
@@ -379,7 +408,11 @@ impl<'a> ResvgRenderer<'a> {
                 let style = text_style(&attrs, &self.theme);
 
                 write!(svg, r#"x="{x:.3}%" class="{class}" style="{style}">"#).unwrap();
-                push_escaped_char(svg, ch);
+
+                match snapshot.grapheme(col, row) {
+                    Some(cluster) => cluster.chars().for_each(|ch| push_escaped_char(svg, ch)),
+                    None => push_escaped_char(svg, ch),
+                }
 
                 svg.push_str("</tspan>");
                 col += cell_width;
@@ -390,11 +423,430 @@ impl<'a> ResvgRenderer<'a> {
 
         svg.push_str("</text>");
     }
+
+    fn push_decorations(
+        &self,
+        svg: &mut String,
+        snapshot: &Snapshot,
+        cursor: Option<(usize, usize)>,
+    ) {
+        for (row, line) in snapshot.lines.iter().enumerate() {
+            let y = row as f64 * self.row_height;
+            let mut col = 0;
+
+            for cell in line.cells() {
+                let cell_width = cell.width() as usize;
+                let pen = cell.pen();
+
+                if cell_width > 0 && (pen.is_underline() || pen.is_strikethrough()) {
+                    let attrs = text_attrs(
+                        pen,
+                        &cursor,
+                        col,
+                        row,
+                        &self.theme,
+                        self.bold_is_bright,
+                        self.cursor_color,
+                    )
+                    .decorate(snapshot.decoration(col, row))
+                    .blink(self.blink_as_bold, snapshot.blink_hidden);
+
+                    let x = col as f64 * self.font_size * 0.6;
+                    svg.push_str(&self.decorations(x, y, cell_width, &attrs));
+                }
+
+                col += cell_width;
+            }
+        }
+    }
+
+    /// A bar, underline or hollow cursor, drawn over the text. Block cursors swap the
+    /// colors of the cell instead.
+    fn push_cursor(&self, svg: &mut String, snapshot: &Snapshot) {
+        let Some((col, row)) = snapshot.cursor else {
+            return;
+        };
+
+        let Some(cell) = cursor_cell(&snapshot.lines, col, row) else {
+            return;
+        };
+
+        let attrs = text_attrs(
+            cell.pen(),
+            &None,
+            col,
+            row,
+            &self.theme,
+            self.bold_is_bright,
+            self.cursor_color,
+        );
+        let (x, y) = (self.x_pct(col), self.y_pct(row));
+        let shape = snapshot.cursor_style.shape;
+
+        if let Some(rect) = self.cursor_rect(x, y, cell.width() as usize, &attrs, shape) {
+            svg.push_str(&rect);
+        }
+    }
+
+    /// Cursor markup for a cell `width` columns wide whose top left corner is
+    /// at `x`, `y` percent of the terminal area.
+    fn cursor_rect(
+        &self,
+        x: f64,
+        y: f64,
+        width: usize,
+        attrs: &TextAttrs,
+        shape: CursorShape,
+    ) -> Option<String> {
+        let t = self.line_thickness;
+        let c = self.cursor_color.unwrap_or_else(|| {
+            attrs
+                .foreground
+                .map_or(self.theme.foreground, |c| color_to_rgb(&c, &self.theme))
+        });
+        let style = format!("fill: rgb({},{},{})", c.r, c.g, c.b);
+
+        match shape {
+            CursorShape::Block => None,
+
+            // Four edges, as the cell width is a percentage and the thickness
+            // isn't.
+            CursorShape::Hollow => {
+                let w = self.cell_width_pct(width);
+                let h = self.row_height;
+
+                Some(format!(
+                    concat!(
+                        r#"<rect x="{x:.3}%" y="{y:.3}%" width="{w:.3}%" height="{t:.3}" style="{style}" />"#,
+                        r#"<rect x="{x:.3}%" y="{y:.3}%" width="{w:.3}%" height="{t:.3}" transform="translate(0 {b:.3})" style="{style}" />"#,
+                        r#"<rect x="{x:.3}%" y="{y:.3}%" width="{t:.3}" height="{h:.3}" style="{style}" />"#,
+                        r#"<rect x="{r:.3}%" y="{y:.3}%" width="{t:.3}" height="{h:.3}" transform="translate(-{t:.3} 0)" style="{style}" />"#,
+                    ),
+                    x = x,
+                    y = y,
+                    w = w,
+                    h = h,
+                    t = t,
+                    b = h - t,
+                    r = x + w,
+                    style = style,
+                ))
+            }
+
+            CursorShape::Underline => Some(format!(
+                r#"<rect x="{x:.3}%" y="{y:.3}%" width="{:.3}%" height="{t:.3}" transform="translate(0 {:.3})" style="{style}" />"#,
+                self.cell_width_pct(width),
+                self.row_height - t,
+            )),
+
+            CursorShape::Bar => Some(format!(
+                r#"<rect x="{x:.3}%" y="{y:.3}%" width="{t:.3}" height="{:.3}" style="{style}" />"#,
+                self.row_height,
+            )),
+        }
+    }
+
+    /// Strikethrough and underline paths, other than plain underlines, for a
+    /// cell `width` columns wide whose top left corner is at `x`, `y` pixels.
+    fn decorations(&self, x: f64, y: f64, width: usize, attrs: &TextAttrs) -> String {
+        let mut svg = String::new();
+        let t = self.line_thickness;
+        // Cells are 0.6em wide, as in the header, with the baseline at 1em.
+        let w = width as f64 * self.font_size * 0.6;
+        let baseline = y + self.font_size;
+        let fg = attrs
+            .foreground
+            .map_or(self.theme.foreground, |c| color_to_rgb(&c, &self.theme));
+
+        let mut push_path = |d: String, color: RGB8, dasharray: Option<f64>| {
+            write!(
+                svg,
+                r#"<path d="{d}" style="fill: none; stroke: rgb({},{},{}); stroke-width: {t:.3}"#,
+                color.r, color.g, color.b
+            )
+            .unwrap();
+
+            if let Some(dash) = dasharray {
+                write!(svg, "; stroke-dasharray: {dash:.3}").unwrap();
+            }
+
+            svg.push_str(r#"" />"#);
+        };
+
+        if let Some(style) = attrs.underline {
+            let color = attrs
+                .underline_color
+                .map_or(fg, |c| color_to_rgb(&c, &self.theme));
+
+            let y = baseline + self.font_size * 0.12;
+
+            match style {
+                UnderlineStyle::Single if attrs.underline_color.is_none() => {}
+
+                UnderlineStyle::Single => {
+                    push_path(format!("M{x:.3} {y:.3}h{w:.3}"), color, None);
+                }
+
+                UnderlineStyle::Double => {
+                    let y2 = y + t * 2.0;
+                    let d = format!("M{x:.3} {y:.3}h{w:.3}M{x:.3} {y2:.3}h{w:.3}");
+                    push_path(d, color, None);
+                }
+
+                // One period of a wave per cell, so that the waves of adjacent
+                // cells join up.
+                UnderlineStyle::Curly => {
+                    let d = format!(
+                        "M{x:.3} {:.3}q{:.3} {:.3} {:.3} 0t{:.3} 0",
+                        y + t,
+                        w / 4.0,
+                        -2.0 * t,
+                        w / 2.0,
+                        w / 2.0
+                    );
+
+                    push_path(d, color, None);
+                }
+
+                UnderlineStyle::Dotted => {
+                    push_path(format!("M{x:.3} {y:.3}h{w:.3}"), color, Some(t));
+                }
+
+                // One dash per cell, centered.
+                UnderlineStyle::Dashed => {
+                    let d = format!("M{:.3} {y:.3}h{:.3}", x + w / 6.0, w * 2.0 / 3.0);
+                    push_path(d, color, None);
+                }
+            }
+        }
+
+        if attrs.strikethrough {
+            let y = baseline - self.font_size * 0.28;
+            push_path(format!("M{x:.3} {y:.3}h{w:.3}"), fg, None);
+        }
+
+        svg
+    }
+
+    /// Opening of a standalone SVG document, up to the terminal area that
+    /// [`Self::svg_for_row`] fragments are placed in.
+    pub fn svg_header(&self) -> &str {
+        &self.header
+    }
+
+    pub fn svg_footer(&self) -> &'static str {
+        Self::footer()
+    }
+
+    /// Vertical position of terminal row `row`, as a percentage of the terminal
+    /// area height.
+    pub fn svg_row_offset(&self, row: usize) -> f64 {
+        self.y_pct(row)
+    }
+
+    /// Backgrounds and text runs of a single terminal row as standalone SVG
+    /// elements, drawn at the top left of the terminal area so that they can be
+    /// reused at any position. Adjacent cells with the same attributes share a
+    /// rect or a text run.
+    pub fn svg_for_row(&self, snapshot: &Snapshot, row: usize) -> SvgRow {
+        let line = &snapshot.lines[row];
+        let cursor = block_cursor(snapshot);
+        let mut backgrounds: Vec<(usize, usize, String)> = Vec::new();
+        let mut runs: Vec<TextRun> = Vec::new();
+        let mut spaces = Vec::new();
+        let mut decorations = Vec::new();
+        let mut col = 0;
+
+        for cell in line.cells() {
+            let pen = cell.pen();
+            let cell_width = cell.width() as usize;
+            let attrs = text_attrs(
+                pen,
+                &cursor,
+                col,
+                row,
+                &self.theme,
+                self.bold_is_bright,
+                self.cursor_color,
+            )
+            .decorate(snapshot.decoration(col, row))
+            .blink(self.blink_as_bold, snapshot.blink_hidden);
+
+            let ch = if attrs.hidden { ' ' } else { cell.char() };
+
+            if attrs.background.is_some() {
+                let style = rect_style(&attrs, &self.theme);
+
+                match backgrounds.last_mut() {
+                    Some((start, width, prev)) if *start + *width == col && *prev == style => {
+                        *width += cell_width;
+                    }
+
+                    _ => backgrounds.push((col, cell_width, style)),
+                }
+            }
+
+            if cell_width > 0 {
+                let svg = self.decorations(0.0, 0.0, cell_width, &attrs);
+
+                if !svg.is_empty() {
+                    decorations.push((self.x_pct(col), svg));
+                }
+            }
+
+            // The zero-width tail of a wide character is neither text nor a
+            // space.
+            if cell_width == 0 {
+                continue;
+            }
+
+            // Spaces are only kept inside a run, so that copied text keeps its
+            // word breaks.
+            if ch == ' ' && !pen.is_underline() {
+                if !runs.is_empty() {
+                    spaces.push(col);
+                }
+
+                col += cell_width;
+                continue;
+            }
+
+            let class = text_class(&attrs);
+            let style = text_style(&attrs, &self.theme);
+
+            // Every character of a cluster gets the cell's position, which
+            // viewers ignore for all but the first.
+            let chars = match snapshot.grapheme(col, row).filter(|_| !attrs.hidden) {
+                Some(cluster) => cluster.chars().map(|ch| (col, ch)).collect(),
+                None => vec![(col, ch)],
+            };
+
+            match runs.last_mut() {
+                Some(run) if run.class == class && run.style == style => {
+                    run.chars.extend(spaces.drain(..).map(|col| (col, ' ')));
+                    run.chars.extend(chars);
+                }
+
+                _ => {
+                    spaces.clear();
+
+                    runs.push(TextRun {
+                        class,
+                        style,
+                        chars,
+                    });
+                }
+            }
+
+            col += cell_width;
+        }
+
+        let backgrounds = backgrounds
+            .into_iter()
+            .map(|(col, width, style)| {
+                let svg = format!(
+                    r#"<rect width="{:.3}%" height="{:.3}" style="{}" shape-rendering="optimizeSpeed" />"#,
+                    self.cell_width_pct(width),
+                    self.row_height,
+                    style
+                );
+
+                (self.x_pct(col), svg)
+            })
+            .collect();
+
+        let mut text: Vec<(f64, String)> = runs
+            .into_iter()
+            .map(|run| {
+                let start = run.chars[0].0;
+
+                let x = run
+                    .chars
+                    .iter()
+                    .map(|(col, _)| format!("{:.3}%", self.x_pct(col - start)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                let mut svg = format!(r#"<text dy="1em" xml:space="preserve" x="{x}""#);
+
+                if !run.class.is_empty() {
+                    write!(svg, r#" class="{}""#, run.class).unwrap();
+                }
+
+                if !run.style.is_empty() {
+                    write!(svg, r#" style="{}""#, run.style).unwrap();
+                }
+
+                svg.push('>');
+
+                for (_, ch) in run.chars {
+                    push_escaped_char(&mut svg, ch);
+                }
+
+                svg.push_str("</text>");
+
+                (self.x_pct(start), svg)
+            })
+            .collect();
+
+        text.extend(decorations);
+
+        if let Some((col, _)) = snapshot.cursor.filter(|(_, r)| *r == row) {
+            if let Some(cell) = cursor_cell(&snapshot.lines, col, row) {
+                let attrs = text_attrs(
+                    cell.pen(),
+                    &None,
+                    col,
+                    row,
+                    &self.theme,
+                    self.bold_is_bright,
+                    self.cursor_color,
+                );
+                let shape = snapshot.cursor_style.shape;
+
+                if let Some(rect) = self.cursor_rect(0.0, 0.0, cell.width() as usize, &attrs, shape)
+                {
+                    text.push((self.x_pct(col), rect));
+                }
+            }
+        }
+
+        SvgRow { backgrounds, text }
+    }
+}
+
+/// The cell at `col`, `row`, skipping the zero-width tail of a wide character.
+fn cursor_cell(lines: &[avt::Line], col: usize, row: usize) -> Option<&avt::Cell> {
+    let mut x = 0;
+
+    for cell in lines.get(row)?.cells() {
+        if x == col && cell.width() > 0 {
+            return Some(cell);
+        }
+
+        x += cell.width() as usize;
+    }
+
+    None
+}
+
+/// SVG elements of a terminal row, each paired with its horizontal offset in
+/// percent.
+pub struct SvgRow {
+    pub backgrounds: Vec<(f64, String)>,
+    pub text: Vec<(f64, String)>,
+}
+
+/// Consecutive glyphs of a row drawn with the same attributes.
+struct TextRun {
+    class: String,
+    style: String,
+    chars: Vec<(usize, char)>,
 }
 
 impl<'a> Renderer for ResvgRenderer<'a> {
     fn render(&mut self, snapshot: &Snapshot) -> ImgVec<RGBA8> {
-        let svg = self.svg_for_frame(&snapshot.lines, snapshot.cursor);
+        let svg = self.svg_for_frame(snapshot);
         let tree = usvg::Tree::from_str(&svg, &self.options).unwrap();
 
         let mut pixmap =
@@ -4,16 +4,19 @@ use std::hash::{Hash, Hasher};
 
 use imgref::ImgVec;
 use log::debug;
-use rgb::RGBA8;
+use rgb::{RGB8, RGBA8};
 use swash::scale::image::{Content, Image};
 use swash::scale::{Render, ScaleContext, Source, StrikeWith};
+use swash::shape::ShapeContext;
+use swash::text::Codepoint;
 use swash::FontRef;
 
-use crate::renderer::{color_to_rgb, text_attrs, Renderer, Settings, TextAttrs};
-use crate::terminal::Snapshot;
+use crate::renderer::{block_cursor, color_to_rgb, text_attrs, Renderer, Settings, TextAttrs};
+use crate::terminal::{CursorShape, Snapshot, UnderlineStyle};
 use crate::theme::Theme;
 
 type CharVariant = (char, bool, bool);
+type ClusterVariant = (String, bool, bool);
 type FontFace = (String, bool, bool);
 
 const POWERLINE_NUDGE: f64 = 0.02;
@@ -34,15 +37,25 @@ struct CellLayout {
     y_b: usize,
     baseline: i32,
     underline_y: usize,
+    strikeout_y: usize,
 }
 
-struct RenderCell {
+struct RenderCell<'a> {
     ch: char,
+    cluster: Option<&'a str>,
     layout: CellLayout,
     attrs: TextAttrs,
     fg: RGBA8,
 }
 
+/// A glyph of a shaped cluster, with its offset from the start of the cell on
+/// the baseline.
+struct ClusterGlyph {
+    image: Image,
+    x: i32,
+    y: i32,
+}
+
 #[derive(Clone, Copy)]
 enum BoxLineStyle {
     Light,
@@ -67,12 +80,17 @@ pub struct SwashRenderer {
     col_width: f64,
     row_height: f64,
     underline_offset: f64,
+    strikeout_offset: f64,
     underline_thickness: f64,
     font_db: fontdb::Database,
     scale_context: ScaleContext,
+    shape_context: ShapeContext,
     glyph_cache: HashMap<CharVariant, Option<Image>>,
+    cluster_cache: HashMap<ClusterVariant, Option<Vec<ClusterGlyph>>>,
     font_id_cache: HashMap<FontFace, Option<fontdb::ID>>,
     bold_is_bright: bool,
+    cursor_color: Option<RGB8>,
+    blink_as_bold: bool,
     hinting: bool,
 }
 
@@ -129,17 +147,31 @@ fn col_width(db: &fontdb::Database, family: &str, font_size: usize) -> Option<f6
     })?
 }
 
-fn underline_metrics(db: &fontdb::Database, family: &str, font_size: usize) -> Option<(f64, f64)> {
+/// Underline offset, strikeout offset and the thickness of both.
+fn line_metrics(db: &fontdb::Database, family: &str, font_size: usize) -> Option<(f64, f64, f64)> {
     let font_id = get_font_id(db, &[family], fontdb::Weight::NORMAL, fontdb::Style::Normal)?;
 
     db.with_face_data(font_id, |font_data, face_index| {
         let font = FontRef::from_index(font_data, face_index as usize)?;
         let metrics = font.metrics(&[]).scale(font_size as f32);
 
-        Some((metrics.underline_offset as f64, metrics.stroke_size as f64))
+        Some((
+            metrics.underline_offset as f64,
+            metrics.strikeout_offset as f64,
+            metrics.stroke_size as f64,
+        ))
     })?
 }
 
+/// Characters that only affect the shaping of the ones around them, which
+/// fonts needn't have glyphs for.
+fn is_default_ignorable(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{200c}' | '\u{200d}' | '\u{fe00}'..='\u{fe0f}' | '\u{e0100}'..='\u{e01ef}'
+    )
+}
+
 fn glyph_image_is_visible(img: &Image) -> bool {
     img.placement.width > 0 && img.placement.height > 0 && !img.data.is_empty()
 }
@@ -165,8 +197,8 @@ impl SwashRenderer {
         let col_width = col_width(&settings.font_db, &settings.text_family, settings.font_size)
             .expect("text_family is guaranteed to resolve by fonts::init");
 
-        let (underline_offset, underline_thickness) =
-            underline_metrics(&settings.font_db, &settings.text_family, settings.font_size)
+        let (underline_offset, strikeout_offset, underline_thickness) =
+            line_metrics(&settings.font_db, &settings.text_family, settings.font_size)
                 .expect("text_family is guaranteed to resolve by fonts::init");
 
         let (cols, rows) = settings.terminal_size;
@@ -183,11 +215,16 @@ impl SwashRenderer {
             col_width,
             row_height,
             underline_offset,
+            strikeout_offset,
             underline_thickness,
             scale_context: ScaleContext::new(),
+            shape_context: ShapeContext::new(),
             font_id_cache: HashMap::new(),
             glyph_cache: HashMap::new(),
+            cluster_cache: HashMap::new(),
             bold_is_bright: settings.bold_is_bright,
+            cursor_color: settings.cursor_color,
+            blink_as_bold: settings.blink_as_bold,
             hinting: settings.hinting,
         }
     }
@@ -212,49 +249,88 @@ impl SwashRenderer {
             return;
         }
 
-        let mut tried_font_ids = HashSet::new();
+        let glyph = self.rasterize(bold, italic, |renderer, font_id| {
+            renderer.rasterize_font_glyph(font_id, ch)
+        });
+
+        self.glyph_cache.insert(key, glyph);
+    }
 
-        if let Some(glyph) = self.rasterize_family_glyph(ch, bold, italic, &mut tried_font_ids) {
-            self.glyph_cache.insert(key, Some(glyph));
+    fn get_glyph(&self, ch: char, bold: bool, italic: bool) -> &Option<Image> {
+        self.glyph_cache
+            .get(&(ch, bold, italic))
+            .expect("caller must invoke ensure_glyph first")
+    }
+
+    fn ensure_cluster(&mut self, cluster: &str, bold: bool, italic: bool) {
+        let key = (cluster.to_owned(), bold, italic);
+
+        if self.cluster_cache.contains_key(&key) {
             return;
         }
 
+        let glyphs = self.rasterize(bold, italic, |renderer, font_id| {
+            renderer.rasterize_font_cluster(font_id, cluster)
+        });
+
+        self.cluster_cache.insert(key, glyphs);
+    }
+
+    fn get_cluster(&self, cluster: &str, bold: bool, italic: bool) -> &Option<Vec<ClusterGlyph>> {
+        self.cluster_cache
+            .get(&(cluster.to_owned(), bold, italic))
+            .expect("caller must invoke ensure_cluster first")
+    }
+
+    /// Rasterize with the first of the configured families, then of the
+    /// other loaded fonts, that can, falling back to regular faces when bold
+    /// or italic ones can't.
+    fn rasterize<T>(
+        &mut self,
+        bold: bool,
+        italic: bool,
+        mut rasterize: impl FnMut(&mut Self, fontdb::ID) -> Option<T>,
+    ) -> Option<T> {
+        let mut tried_font_ids = HashSet::new();
+
+        if let Some(glyph) =
+            self.rasterize_family_glyph(bold, italic, &mut tried_font_ids, &mut rasterize)
+        {
+            return Some(glyph);
+        }
+
         if bold || italic {
-            if let Some(glyph) = self.rasterize_family_glyph(ch, false, false, &mut tried_font_ids)
+            if let Some(glyph) =
+                self.rasterize_family_glyph(false, false, &mut tried_font_ids, &mut rasterize)
             {
-                self.glyph_cache.insert(key, Some(glyph));
-                return;
+                return Some(glyph);
             }
         }
 
-        if let Some(glyph) = self.rasterize_fallback_glyph(ch, bold, italic, &tried_font_ids) {
-            self.glyph_cache.insert(key, Some(glyph));
-            return;
+        if let Some(glyph) =
+            self.rasterize_fallback_glyph(bold, italic, &tried_font_ids, &mut rasterize)
+        {
+            return Some(glyph);
         }
 
         if bold || italic {
-            if let Some(glyph) = self.rasterize_fallback_glyph(ch, false, false, &tried_font_ids) {
-                self.glyph_cache.insert(key, Some(glyph));
-                return;
+            if let Some(glyph) =
+                self.rasterize_fallback_glyph(false, false, &tried_font_ids, &mut rasterize)
+            {
+                return Some(glyph);
             }
         }
 
-        self.glyph_cache.insert(key, None);
-    }
-
-    fn get_glyph(&self, ch: char, bold: bool, italic: bool) -> &Option<Image> {
-        self.glyph_cache
-            .get(&(ch, bold, italic))
-            .expect("caller must invoke ensure_glyph first")
+        None
     }
 
-    fn rasterize_family_glyph(
+    fn rasterize_family_glyph<T>(
         &mut self,
-        ch: char,
         bold: bool,
         italic: bool,
         tried_font_ids: &mut HashSet<fontdb::ID>,
-    ) -> Option<Image> {
+        rasterize: &mut impl FnMut(&mut Self, fontdb::ID) -> Option<T>,
+    ) -> Option<T> {
         let families = self.font_families.clone();
 
         for name in &families {
@@ -264,7 +340,7 @@ impl SwashRenderer {
 
             tried_font_ids.insert(font_id);
 
-            if let Some(glyph) = self.rasterize_font_glyph(font_id, ch) {
+            if let Some(glyph) = rasterize(self, font_id) {
                 return Some(glyph);
             }
         }
@@ -272,13 +348,13 @@ impl SwashRenderer {
         None
     }
 
-    fn rasterize_fallback_glyph(
+    fn rasterize_fallback_glyph<T>(
         &mut self,
-        ch: char,
         bold: bool,
         italic: bool,
         tried_font_ids: &HashSet<fontdb::ID>,
-    ) -> Option<Image> {
+        rasterize: &mut impl FnMut(&mut Self, fontdb::ID) -> Option<T>,
+    ) -> Option<T> {
         let weight = font_weight(bold);
         let style = font_style(italic);
 
@@ -294,7 +370,7 @@ impl SwashRenderer {
             .collect();
 
         for font_id in fallback_font_ids {
-            if let Some(glyph) = self.rasterize_font_glyph(font_id, ch) {
+            if let Some(glyph) = rasterize(self, font_id) {
                 return Some(glyph);
             }
         }
@@ -331,6 +407,73 @@ impl SwashRenderer {
             })?
     }
 
+    /// Shape `text` with the font and rasterize the resulting glyphs, unless
+    /// the font lacks any of its characters.
+    fn rasterize_font_cluster(
+        &mut self,
+        font_id: fontdb::ID,
+        text: &str,
+    ) -> Option<Vec<ClusterGlyph>> {
+        let font_size = self.font_size as f32;
+        let hinting = self.hinting;
+        let scale_context = &mut self.scale_context;
+        let shape_context = &mut self.shape_context;
+
+        self.font_db
+            .with_face_data(font_id, |font_data, face_index| {
+                let font = FontRef::from_index(font_data, face_index as usize)?;
+                let charmap = font.charmap();
+
+                if text
+                    .chars()
+                    .any(|ch| charmap.map(ch) == 0 && !is_default_ignorable(ch))
+                {
+                    return None;
+                }
+
+                let mut shaper = shape_context
+                    .builder(font)
+                    .script(text.chars().next()?.script())
+                    .size(font_size)
+                    .build();
+
+                shaper.add_str(text);
+                let mut glyphs = Vec::new();
+                let mut advance = 0.0;
+
+                shaper.shape_with(|cluster| {
+                    for glyph in cluster.glyphs {
+                        glyphs.push((glyph.id, advance + glyph.x, glyph.y));
+                        advance += glyph.advance;
+                    }
+                });
+
+                let mut scaler = scale_context
+                    .builder_with_id(font, font_id_key(font_id))
+                    .size(font_size)
+                    .hint(hinting)
+                    .build();
+
+                let glyphs: Vec<_> = glyphs
+                    .into_iter()
+                    .filter(|(glyph_id, _, _)| *glyph_id != 0)
+                    .filter_map(|(glyph_id, x, y)| {
+                        let image = Render::new(GLYPH_SOURCES)
+                            .render(&mut scaler, glyph_id)
+                            .filter(glyph_image_is_visible)?;
+
+                        Some(ClusterGlyph {
+                            image,
+                            x: x.round() as i32,
+                            y: y.round() as i32,
+                        })
+                    })
+                    .collect();
+
+                (!glyphs.is_empty()).then_some(glyphs)
+            })?
+    }
+
     fn new_frame(&self) -> Vec<RGBA8> {
         vec![self.theme.background.with_alpha(255); self.pixel_width * self.pixel_height]
     }
@@ -353,6 +496,7 @@ impl SwashRenderer {
             y_b: margin_t + ((row + 1) as f64 * self.row_height).round() as usize,
             baseline: baseline as i32,
             underline_y: (baseline - self.underline_offset).round() as usize,
+            strikeout_y: (baseline - self.strikeout_offset).round() as usize,
         }
     }
 
@@ -379,20 +523,117 @@ impl SwashRenderer {
         }
     }
 
-    fn paint_underline(&self, buf: &mut [RGBA8], layout: CellLayout, fg: RGBA8, underline: bool) {
-        if !underline {
+    fn paint_underline(&self, buf: &mut [RGBA8], layout: CellLayout, attrs: &TextAttrs, fg: RGBA8) {
+        let Some(style) = attrs.underline else {
             return;
-        }
+        };
+
+        let fg = attrs
+            .underline_color
+            .map_or(fg, |c| color_to_rgb(&c, &self.theme).with_alpha(255));
 
         let thickness = (self.underline_thickness.round() as usize).max(1);
+        let CellLayout { x_l, x_r, .. } = layout;
+        let y = layout.underline_y;
 
-        for dy in 0..thickness {
-            let y = layout.underline_y + dy;
-            let idx = y * self.pixel_width;
-            buf[idx + layout.x_l..idx + layout.x_r].fill(fg);
+        match style {
+            UnderlineStyle::Single => {
+                self.paint_cell_rect(buf, (x_l, y, x_r, y + thickness), fg, false);
+            }
+
+            UnderlineStyle::Double => {
+                let y2 = y + thickness * 2;
+                self.paint_cell_rect(buf, (x_l, y, x_r, y + thickness), fg, false);
+                self.paint_cell_rect(buf, (x_l, y2, x_r, y2 + thickness), fg, false);
+            }
+
+            // One period of a wave per cell, so that the waves of adjacent
+            // cells join up.
+            UnderlineStyle::Curly => {
+                let amplitude = (thickness * 2) as f64;
+                let width = (x_r - x_l).max(1) as f64;
+
+                for x in x_l..x_r {
+                    let phase = (x - x_l) as f64 / width * std::f64::consts::TAU;
+                    let dy = ((1.0 - phase.cos()) / 2.0 * amplitude).round() as usize;
+                    self.paint_cell_rect(buf, (x, y + dy, x + 1, y + dy + thickness), fg, false);
+                }
+            }
+
+            UnderlineStyle::Dotted => {
+                for x in (x_l..x_r).step_by(thickness * 2) {
+                    let x_end = (x + thickness).min(x_r);
+                    self.paint_cell_rect(buf, (x, y, x_end, y + thickness), fg, false);
+                }
+            }
+
+            // One dash per cell, centered.
+            UnderlineStyle::Dashed => {
+                let gap = (x_r - x_l) / 6;
+                self.paint_cell_rect(buf, (x_l + gap, y, x_r - gap, y + thickness), fg, false);
+            }
         }
     }
 
+    fn paint_strikethrough(
+        &self,
+        buf: &mut [RGBA8],
+        layout: CellLayout,
+        attrs: &TextAttrs,
+        fg: RGBA8,
+    ) {
+        if !attrs.strikethrough {
+            return;
+        }
+
+        let thickness = (self.underline_thickness.round() as usize).max(1);
+        let y = layout.strikeout_y;
+
+        self.paint_cell_rect(
+            buf,
+            (layout.x_l, y, layout.x_r, y + thickness),
+            fg,
+            attrs.faint,
+        );
+    }
+
+    fn paint_cursor(&self, buf: &mut [RGBA8], layout: CellLayout, fg: RGBA8, shape: CursorShape) {
+        let thickness = self.box_thickness();
+        let fg = self.cursor_color.map_or(fg, |c| c.with_alpha(255));
+
+        let rect = match shape {
+            CursorShape::Block => return,
+
+            CursorShape::Hollow => {
+                let CellLayout {
+                    x_l, x_r, y_t, y_b, ..
+                } = layout;
+
+                for rect in [
+                    (x_l, y_t, x_r, y_t + thickness),
+                    (x_l, y_b.saturating_sub(thickness), x_r, y_b),
+                    (x_l, y_t, x_l + thickness, y_b),
+                    (x_r.saturating_sub(thickness), y_t, x_r, y_b),
+                ] {
+                    self.paint_cell_rect(buf, rect, fg, false);
+                }
+
+                return;
+            }
+
+            CursorShape::Underline => (
+                layout.x_l,
+                layout.y_b.saturating_sub(thickness),
+                layout.x_r,
+                layout.y_b,
+            ),
+
+            CursorShape::Bar => (layout.x_l, layout.y_t, layout.x_l + thickness, layout.y_b),
+        };
+
+        self.paint_cell_rect(buf, rect, fg, false);
+    }
+
     fn box_thickness(&self) -> usize {
         (self.underline_thickness.ceil() as usize).max(1)
     }
@@ -1040,17 +1281,52 @@ impl SwashRenderer {
         fg: RGBA8,
     ) {
         self.ensure_glyph(ch, attrs.bold, attrs.italic);
-        let glyph = self.get_glyph(ch, attrs.bold, attrs.italic);
 
-        let Some(glyph) = glyph.as_ref() else {
+        if let Some(glyph) = self.get_glyph(ch, attrs.bold, attrs.italic) {
+            self.paint_glyph_image(buf, glyph, layout.x_l as i32, layout.baseline, attrs, fg);
+        }
+    }
+
+    /// Paint a cluster of more than one character, or just its first
+    /// character when no font has all of them.
+    fn paint_cluster(
+        &mut self,
+        buf: &mut [RGBA8],
+        cluster: &str,
+        layout: CellLayout,
+        attrs: &TextAttrs,
+        fg: RGBA8,
+    ) {
+        self.ensure_cluster(cluster, attrs.bold, attrs.italic);
+
+        let Some(glyphs) = self.get_cluster(cluster, attrs.bold, attrs.italic) else {
+            let ch = cluster.chars().next().unwrap();
+            self.paint_glyph(buf, ch, layout, attrs, fg);
+
             return;
         };
 
+        for glyph in glyphs {
+            let x = layout.x_l as i32 + glyph.x;
+            let baseline = layout.baseline - glyph.y;
+            self.paint_glyph_image(buf, &glyph.image, x, baseline, attrs, fg);
+        }
+    }
+
+    fn paint_glyph_image(
+        &self,
+        buf: &mut [RGBA8],
+        glyph: &Image,
+        x: i32,
+        baseline: i32,
+        attrs: &TextAttrs,
+        fg: RGBA8,
+    ) {
         let placement = glyph.placement;
         let width = placement.width as usize;
         let height = placement.height as usize;
-        let y_offset = layout.baseline - placement.top;
-        let x_offset = layout.x_l as i32 + placement.left;
+        let y_offset = baseline - placement.top;
+        let x_offset = x + placement.left;
 
         match glyph.content {
             Content::Mask => {
@@ -1238,7 +1514,9 @@ impl Renderer for SwashRenderer {
         let mut buf = self.new_frame();
         let margin_l = self.col_width;
         let margin_t = (self.row_height / 2.0).round() as usize;
+        let block_cursor = block_cursor(snapshot);
         let mut cells = Vec::new();
+        let mut cursor = None;
 
         for (row, line) in snapshot.lines.iter().enumerate() {
             let mut col = 0;
@@ -1250,17 +1528,25 @@ impl Renderer for SwashRenderer {
 
                 let attrs = text_attrs(
                     cell.pen(),
-                    &snapshot.cursor,
+                    &block_cursor,
                     col,
                     row,
                     &self.theme,
                     self.bold_is_bright,
-                );
+                    self.cursor_color,
+                )
+                .decorate(snapshot.decoration(col, row))
+                .blink(self.blink_as_bold, snapshot.blink_hidden);
 
                 let fg = self.foreground(&attrs);
 
+                if cell_width > 0 && snapshot.cursor == Some((col, row)) {
+                    cursor = Some((layout, fg));
+                }
+
                 cells.push(RenderCell {
-                    ch,
+                    ch: if attrs.hidden { ' ' } else { ch },
+                    cluster: snapshot.grapheme(col, row).filter(|_| !attrs.hidden),
                     layout,
                     attrs,
                     fg,
@@ -1275,11 +1561,13 @@ impl Renderer for SwashRenderer {
         }
 
         for cell in &cells {
-            self.paint_underline(&mut buf, cell.layout, cell.fg, cell.attrs.underline);
+            self.paint_underline(&mut buf, cell.layout, &cell.attrs, cell.fg);
         }
 
         for cell in &cells {
-            if cell.ch != ' '
+            if let Some(cluster) = cell.cluster {
+                self.paint_cluster(&mut buf, cluster, cell.layout, &cell.attrs, cell.fg);
+            } else if cell.ch != ' '
                 && !self.paint_powerline_symbol(
                     &mut buf,
                     cell.ch,
@@ -1293,6 +1581,14 @@ impl Renderer for SwashRenderer {
             }
         }
 
+        for cell in &cells {
+            self.paint_strikethrough(&mut buf, cell.layout, &cell.attrs, cell.fg);
+        }
+
+        if let Some((layout, fg)) = cursor {
+            self.paint_cursor(&mut buf, layout, fg, snapshot.cursor_style.shape);
+        }
+
         ImgVec::new(buf, self.pixel_width, self.pixel_height)
     }
 
@@ -0,0 +1,154 @@
+//! A machine-readable summary of a run, for checking many renders at once
+//! without scraping log lines.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use log::warn;
+use serde::Serialize;
+
+use crate::Output;
+
+/// Filled in as the run goes; parts the run never gets to, e.g. fonts for text
+/// outputs, are left empty.
+#[derive(Default, Serialize)]
+pub struct Report {
+    pub theme: Option<String>,
+    pub font_families: Vec<String>,
+    pub text_font_family: Option<String>,
+    pub text_font_monospaced: Option<bool>,
+    pub terminal_size: Option<TerminalSize>,
+    /// Terminal size fitting every resize in the recording.
+    pub canvas_size: Option<TerminalSize>,
+    /// Rendered image size in pixels.
+    pub pixel_size: Option<Size>,
+    pub frame_count: u64,
+    /// Length of a single play in seconds, including the last frame.
+    pub duration: f64,
+    outputs: Vec<OutputReport>,
+    /// Seconds spent rendering frames to images.
+    render_time: f64,
+    /// Seconds spent encoding, i.e. writing outputs, outside of rendering.
+    encode_time: f64,
+    warnings: Vec<String>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub struct TerminalSize {
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl From<(usize, usize)> for TerminalSize {
+    fn from((cols, rows): (usize, usize)) -> Self {
+        Self { cols, rows }
+    }
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub struct Size {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl From<(usize, usize)> for Size {
+    fn from((width, height): (usize, usize)) -> Self {
+        Self { width, height }
+    }
+}
+
+#[derive(Serialize)]
+struct OutputReport {
+    path: PathBuf,
+    format: String,
+    /// Bytes written, counting every file of a directory of PNG frames.
+    size: u64,
+}
+
+impl Report {
+    /// Log `message` as a warning and keep it for the report.
+    pub fn warn(&mut self, message: String) {
+        warn!("{message}");
+        self.warnings.push(message);
+    }
+
+    pub fn add_time(&mut self, render_time: Duration, total_time: Duration) {
+        self.render_time += render_time.as_secs_f64();
+        self.encode_time += total_time.saturating_sub(render_time).as_secs_f64();
+    }
+
+    /// Record the size of every output written.
+    pub fn add_outputs(&mut self, outputs: &[Output], poster: Option<&Path>) -> Result<()> {
+        let paths = outputs
+            .iter()
+            .map(|o| (o.path.as_path(), format_name(o.format)))
+            .chain(poster.map(|path| (path, "png".to_owned())));
+
+        for (path, format) in paths {
+            self.outputs.push(OutputReport {
+                path: path.to_owned(),
+                format,
+                size: file_size(path)?,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<()> {
+        serde_json::to_writer_pretty(&mut *output, self)?;
+        writeln!(output)?;
+
+        Ok(())
+    }
+}
+
+fn format_name(format: crate::Format) -> String {
+    format
+        .to_possible_value()
+        .map_or_else(|| format!("{format:?}"), |v| v.get_name().to_owned())
+}
+
+fn file_size(path: &Path) -> Result<u64> {
+    let metadata = fs::metadata(path)?;
+
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    fs::read_dir(path)?.try_fold(0, |size, entry| Ok(size + file_size(&entry?.path())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_warnings_and_splits_time() {
+        let mut report = Report {
+            theme: Some("dracula".to_owned()),
+            frame_count: 2,
+            ..Default::default()
+        };
+
+        report.warn("first font family is not monospaced".to_owned());
+        report.add_time(Duration::from_millis(250), Duration::from_millis(1000));
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(json["theme"], "dracula");
+        assert_eq!(json["frame_count"], 2);
+        assert_eq!(json["render_time"], 0.25);
+        assert_eq!(json["encode_time"], 0.75);
+        assert_eq!(
+            json["warnings"],
+            serde_json::json!(["first font family is not monospaced"])
+        );
+    }
+}
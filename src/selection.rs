@@ -68,7 +68,8 @@ pub fn resolve(spec: &SelectionSpec, summary: &timeline::Summary) -> Result<Sele
     }
 }
 
-fn resolve_position(position: &TimelinePosition, summary: &timeline::Summary) -> Result<f64> {
+/// Resolve a single position to a timestamp on the adjusted timeline.
+pub fn resolve_position(position: &TimelinePosition, summary: &timeline::Summary) -> Result<f64> {
     match position {
         TimelinePosition::Time(t) => {
             if *t > summary.duration() {
@@ -115,6 +116,8 @@ fn resolve_position(position: &TimelinePosition, summary: &timeline::Summary) ->
             }
         }
 
+        TimelinePosition::Last => Ok(summary.duration()),
+
         TimelinePosition::EventIndex(i) => {
             summary.event_times().get(*i).copied().ok_or_else(|| {
                 anyhow!(
@@ -296,6 +299,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolve_last_to_recording_duration() {
+        let events = [output(2.0, "a"), output(10.0, "b")];
+
+        assert_eq!(
+            plan("last", &events).unwrap(),
+            SelectionPlan::Positions(vec![10.0])
+        );
+    }
+
     #[test]
     fn resolve_rejects_range_start_after_end() {
         let events = [output(2.0, "a"), output(10.0, "b")];
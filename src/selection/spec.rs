@@ -26,6 +26,8 @@ pub enum TimelinePosition {
     MarkerIndex(usize),
     MarkerPrefix(String),
     EventIndex(usize),
+    /// The end of the recording.
+    Last,
 }
 
 /// The default selection is the whole recording.
@@ -68,6 +70,14 @@ impl FromStr for SelectionSpec {
     }
 }
 
+impl FromStr for TimelinePosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_position(s)
+    }
+}
+
 fn parse_bound(s: &str) -> Result<Option<TimelinePosition>, String> {
     if s.is_empty() {
         Ok(None)
@@ -77,6 +87,10 @@ fn parse_bound(s: &str) -> Result<Option<TimelinePosition>, String> {
 }
 
 fn parse_position(s: &str) -> Result<TimelinePosition, String> {
+    if s == "last" {
+        return Ok(TimelinePosition::Last);
+    }
+
     if let Some(value) = s.strip_prefix("marker:") {
         if value.is_empty() {
             return Err("empty marker value".to_string());
@@ -315,6 +329,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parses_last_keyword() {
+        assert_eq!(parse_position("last").unwrap(), Last);
+        assert_eq!(
+            spec("5..last"),
+            SelectionSpec::Range {
+                start: Some(Time(5.0)),
+                end: Some(Last)
+            }
+        );
+    }
+
     #[test]
     fn parses_percent_positions() {
         assert_eq!(parse_position("0%").unwrap(), Percent(0.0));
@@ -1,31 +1,1567 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
 use avt::Vt;
+use clap::ValueEnum;
+use swash::text::{ClusterBreak, Codepoint};
+
+/// A virtual terminal along with the state that avt doesn't keep track of,
+/// such as the cursor style, underline styles and colors, and grapheme
+/// clusters.
+pub struct Terminal {
+    vt: Vt,
+    scrollback_limit: Option<usize>,
+    /// A second terminal fed the same output, with SGR sequences rewritten to
+    /// carry the underline style as the background color and the underline
+    /// color as the foreground color, so that its cells line up with `vt`'s.
+    /// It starts as a copy of `vt` once the output first selects an underline
+    /// style or color.
+    decorations: Option<Vt>,
+    /// A third terminal fed the same output without SGR sequences, where the
+    /// first character of each cluster of more than one character is printed
+    /// with the cluster's index in `graphemes` as the foreground color. It
+    /// starts as a copy of `vt` once the output first prints such a cluster.
+    clusters: Option<Vt>,
+    cursor_style: CursorStyle,
+    scanner: Scanner,
+    graphemes: Graphemes,
+    /// Column of the cluster that ended the last output, which the next
+    /// output may extend.
+    open_col: usize,
+}
+
+/// Cursor shapes selected with DECSCUSR (`CSI Ps SP q`), plus a hollow box
+/// that can only be forced with `--cursor-style`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum CursorShape {
+    #[default]
+    Block,
+    Underline,
+    Bar,
+    /// Outline of the cell, leaving its colors alone
+    Hollow,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+impl Default for CursorStyle {
+    /// A blinking block, as in xterm.
+    fn default() -> Self {
+        CursorStyle {
+            shape: CursorShape::Block,
+            blinking: true,
+        }
+    }
+}
+
+impl CursorStyle {
+    fn from_decscusr(ps: u16) -> Option<Self> {
+        let (shape, blinking) = match ps {
+            0 | 1 => (CursorShape::Block, true),
+            2 => (CursorShape::Block, false),
+            3 => (CursorShape::Underline, true),
+            4 => (CursorShape::Underline, false),
+            5 => (CursorShape::Bar, true),
+            6 => (CursorShape::Bar, false),
+            _ => return None,
+        };
+
+        Some(CursorStyle { shape, blinking })
+    }
+
+    fn decscusr(&self) -> u16 {
+        let ps = match self.shape {
+            CursorShape::Block | CursorShape::Hollow => 1,
+            CursorShape::Underline => 3,
+            CursorShape::Bar => 5,
+        };
+
+        if self.blinking {
+            ps
+        } else {
+            ps + 1
+        }
+    }
+}
+
+/// Underline styles selected with SGR 4:x.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UnderlineStyle {
+    #[default]
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+/// Cell attributes that avt's pen doesn't have.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Decoration {
+    pub underline_style: UnderlineStyle,
+    pub underline_color: Option<avt::Color>,
+}
+
+impl Decoration {
+    fn from_pen(pen: &avt::Pen) -> Self {
+        let underline_style = match pen.background() {
+            Some(avt::Color::Indexed(2)) => UnderlineStyle::Double,
+            Some(avt::Color::Indexed(3)) => UnderlineStyle::Curly,
+            Some(avt::Color::Indexed(4)) => UnderlineStyle::Dotted,
+            Some(avt::Color::Indexed(5)) => UnderlineStyle::Dashed,
+            _ => UnderlineStyle::Single,
+        };
+
+        Decoration {
+            underline_style,
+            underline_color: pen.foreground(),
+        }
+    }
+
+    /// A sequence selecting the decoration, for cells that are underlined
+    /// already when it has an underline style.
+    fn sgr(&self) -> String {
+        let mut params = Vec::new();
+
+        match self.underline_style {
+            UnderlineStyle::Single => {}
+            UnderlineStyle::Double => params.push("4:2".to_owned()),
+            UnderlineStyle::Curly => params.push("4:3".to_owned()),
+            UnderlineStyle::Dotted => params.push("4:4".to_owned()),
+            UnderlineStyle::Dashed => params.push("4:5".to_owned()),
+        }
+
+        match self.underline_color {
+            Some(avt::Color::Indexed(n)) => params.push(format!("58;5;{n}")),
+            Some(avt::Color::RGB(c)) => params.push(format!("58;2;{};{};{}", c.r, c.g, c.b)),
+            None => {}
+        }
+
+        sgr(&params)
+    }
+
+    /// A sequence going back to the default decoration, leaving the underline
+    /// itself alone.
+    fn reset_sgr(&self) -> String {
+        let mut params = Vec::new();
+
+        if self.underline_style != UnderlineStyle::Single {
+            params.push("4".to_owned());
+        }
+
+        if self.underline_color.is_some() {
+            params.push("59".to_owned());
+        }
+
+        sgr(&params)
+    }
+}
+
+fn sgr(params: &[String]) -> String {
+    if params.is_empty() {
+        String::new()
+    } else {
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
+
+/// Picks the escape sequences avt ignores or misreads out of the output,
+/// keeping its place when a sequence is split between output events. The
+/// start of a sequence is held back until it's complete.
+#[derive(Default)]
+enum Scanner {
+    #[default]
+    Ground,
+    Escape,
+    Csi {
+        params: String,
+        intermediates: String,
+    },
+}
+
+/// Output split between a terminal's three avt instances, and the cursor
+/// style it selects.
+#[derive(Default)]
+struct Scanned {
+    vt: String,
+    /// Output for the decoration terminal, once there is one.
+    decorations: Option<String>,
+    /// Where in `vt` the output starts selecting decorations, when it's the
+    /// first output to do so.
+    decorations_from: usize,
+    /// Output for the cluster terminal, once there is one.
+    clusters: Option<String>,
+    /// Where in `vt` the output starts printing clusters of more than one
+    /// character, when it's the first output to do so.
+    clusters_from: usize,
+    cursor_style: Option<CursorStyle>,
+    /// Whether `clusters` starts by printing the cluster that ended the last
+    /// output again, extended by characters from this one.
+    restamp: bool,
+    /// Where the cluster that ends this output starts in `vt`, when it
+    /// doesn't start in an earlier output.
+    tail: Option<usize>,
+}
+
+impl Scanned {
+    fn push_str(&mut self, data: &str) {
+        self.vt.push_str(data);
+
+        if let Some(decorations) = &mut self.decorations {
+            decorations.push_str(data);
+        }
+
+        if let Some(clusters) = &mut self.clusters {
+            clusters.push_str(data);
+        }
+    }
+
+    /// Pass on a CSI sequence, picking out the cursor style and the
+    /// decorations it selects.
+    fn csi(&mut self, csi: &Csi) {
+        match (csi.c, csi.intermediates) {
+            ('q', " ") => {
+                let ps = if csi.params.is_empty() {
+                    Some(0)
+                } else {
+                    csi.params.parse().ok()
+                };
+
+                if let Some(s) = ps.and_then(CursorStyle::from_decscusr) {
+                    self.cursor_style = Some(s);
+                }
+
+                self.push_str(csi.text);
+            }
+
+            _ if csi.is_sgr() => self.push_sgr(csi),
+            _ => self.push_str(csi.text),
+        }
+    }
+
+    fn push_sgr(&mut self, csi: &Csi) {
+        // Most SGR sequences have nothing for avt to misread, and nothing to
+        // decorate cells with until the output selects an underline style or
+        // color.
+        if self.decorations.is_none() && csi.is_plain_sgr() {
+            self.vt.push_str(csi.text);
+
+            return;
+        }
+
+        let (vt, decorations) = split_sgr(csi.params);
+
+        if !vt.is_empty() {
+            self.vt.push_str(&sgr(&vt));
+        }
+
+        if self.decorations.is_none()
+            && decorations
+                .iter()
+                .any(|param| param.starts_with("38") || param.starts_with("48"))
+        {
+            self.decorations = Some(String::new());
+            self.decorations_from = self.vt.len();
+        }
+
+        if let Some(out) = &mut self.decorations {
+            out.push_str(&sgr(&decorations));
+        }
+    }
+}
+
+/// Grapheme clusters of more than one character, which avt would spread over
+/// as many cells, and the cluster being printed.
+///
+/// Like in most terminals, characters that extend a cluster (combining marks,
+/// emoji modifiers and ZWJ sequences, the second half of a flag) don't move
+/// the cursor, so only the first character of a cluster reaches avt. The
+/// cluster terminal gets it when the cluster is complete.
+#[derive(Default)]
+struct Graphemes {
+    clusters: Vec<String>,
+    ids: HashMap<String, usize>,
+    open: Option<Cluster>,
+}
+
+struct Cluster {
+    first: char,
+    /// The characters after the first one, if any.
+    rest: String,
+    /// Where the cluster starts in the output for `vt`.
+    at: usize,
+    last: ClusterBreak,
+    /// Whether the cluster is an emoji that a ZWJ may join with another.
+    pictographic: bool,
+    /// Whether the cluster ended an earlier output and is printed already.
+    carried: bool,
+    /// Whether characters were added since it was printed.
+    extended: bool,
+}
+
+impl Cluster {
+    fn new(c: char, at: usize) -> Self {
+        Cluster {
+            first: c,
+            rest: String::new(),
+            at,
+            last: c.cluster_break(),
+            pictographic: c.is_extended_pictographic(),
+            carried: false,
+            extended: false,
+        }
+    }
+
+    /// Whether `c` continues the cluster, following the grapheme cluster
+    /// boundary rules of UAX #29.
+    fn extends(&self, c: char) -> bool {
+        use ClusterBreak::*;
+
+        match (self.last, c.cluster_break()) {
+            (_, EX | ZWJ | SM) | (PP, _) => true,
+            (L, L | V | LV | LVT) | (LV | V, V | T) | (LVT | T, T) => true,
+            (ZWJ, _) => self.pictographic && c.is_extended_pictographic(),
+            (RI, RI) => self.rest.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        let class = c.cluster_break();
+
+        self.pictographic &=
+            matches!(class, ClusterBreak::EX | ClusterBreak::ZWJ) || c.is_extended_pictographic();
+        self.last = class;
+        self.extended = true;
+        self.rest.push(c);
+    }
+}
+
+impl Graphemes {
+    /// Pass on output from `verbatim`, ending with the character printed at
+    /// `last`, unless the open cluster needs more than that.
+    fn pass(&mut self, data: &str, last: Option<usize>, scanned: &mut Scanned) -> bool {
+        if self
+            .open
+            .as_ref()
+            .is_some_and(|cluster| cluster.last == ClusterBreak::PP || cluster.extended)
+        {
+            return false;
+        }
+
+        self.close(scanned);
+        let at = scanned.vt.len();
+        scanned.vt.push_str(data);
+        self.open = last.map(|i| Cluster::new(char::from(data.as_bytes()[i]), at + i));
+
+        true
+    }
+
+    fn print(&mut self, c: char, scanned: &mut Scanned) {
+        if let Some(cluster) = self.open.as_mut().filter(|cluster| cluster.extends(c)) {
+            cluster.push(c);
+
+            return;
+        }
+
+        self.close(scanned);
+        self.open = Some(Cluster::new(c, scanned.vt.len()));
+        scanned.vt.push(c);
+
+        if let Some(decorations) = &mut scanned.decorations {
+            decorations.push(c);
+        }
+    }
+
+    fn close(&mut self, scanned: &mut Scanned) {
+        if let Some(cluster) = self.open.take() {
+            self.stamp(&cluster, scanned);
+        }
+    }
+
+    /// Keep the cluster that ends the output open to the next one.
+    fn finish(&mut self, scanned: &mut Scanned) {
+        if let Some(mut cluster) = self.open.take() {
+            self.stamp(&cluster, scanned);
+
+            if !cluster.carried {
+                scanned.tail = Some(cluster.at);
+            }
+
+            cluster.carried = true;
+            cluster.extended = false;
+            cluster.at = 0;
+            self.open = Some(cluster);
+        }
+    }
+
+    /// Print the cluster in the cluster terminal.
+    fn stamp(&mut self, cluster: &Cluster, scanned: &mut Scanned) {
+        if cluster.carried && !cluster.extended {
+            return;
+        }
+
+        scanned.restamp |= cluster.carried;
+        let first = cluster.first;
+
+        if cluster.rest.is_empty() {
+            if let Some(clusters) = &mut scanned.clusters {
+                clusters.push(first);
+            }
+
+            return;
+        }
+
+        let text = format!("{first}{}", cluster.rest);
+
+        let id = match self.ids.get(&text) {
+            Some(id) => *id,
+
+            None => {
+                self.clusters.push(text.clone());
+                self.ids.insert(text, self.clusters.len() - 1);
+
+                self.clusters.len() - 1
+            }
+        };
+
+        let clusters = scanned.clusters.get_or_insert_with(|| {
+            scanned.clusters_from = cluster.at;
+
+            String::new()
+        });
+
+        write!(
+            clusters,
+            "\x1b[38;2;{};{};{}m{first}\x1b[39m",
+            (id >> 16) & 0xff,
+            (id >> 8) & 0xff,
+            id & 0xff
+        )
+        .unwrap();
+    }
+}
+
+impl Scanner {
+    /// Feed `data`, adding the output for each avt instance to `scanned`, and
+    /// the cursor style of the last DECSCUSR sequence in it, or the default
+    /// style after a terminal reset. Output for the decoration and cluster
+    /// terminals starts when `data` first needs them.
+    fn feed(&mut self, data: &str, graphemes: &mut Graphemes, scanned: &mut Scanned) {
+        let mut rest = data;
+
+        while let Some(c) = rest.chars().next() {
+            if let Scanner::Ground = self {
+                if scanned.decorations.is_none() && scanned.clusters.is_none() {
+                    let (len, last) = verbatim(rest);
+
+                    if len > 0 && graphemes.pass(&rest[..len], last, scanned) {
+                        rest = &rest[len..];
+
+                        continue;
+                    }
+                }
+
+                if let Some(csi) = Csi::parse(rest) {
+                    graphemes.close(scanned);
+                    scanned.csi(&csi);
+                    rest = &rest[csi.text.len()..];
+
+                    continue;
+                }
+            }
+
+            rest = &rest[c.len_utf8()..];
+
+            *self = match (std::mem::take(self), c) {
+                (held, '\x1b') => {
+                    graphemes.close(scanned);
+                    held.push_held(scanned);
+
+                    Scanner::Escape
+                }
+
+                (held, '\x18' | '\x1a') => {
+                    graphemes.close(scanned);
+                    held.push_held(scanned);
+                    scanned.push_str(c.encode_utf8(&mut [0; 4]));
+
+                    Scanner::Ground
+                }
+
+                (Scanner::Escape, '[') => Scanner::Csi {
+                    params: String::new(),
+                    intermediates: String::new(),
+                },
+
+                (Scanner::Escape, 'c') => {
+                    scanned.push_str("\x1bc");
+                    scanned.cursor_style = Some(CursorStyle::default());
+
+                    Scanner::Ground
+                }
+
+                (Scanner::Escape, _) => {
+                    scanned.push_str("\x1b");
+                    scanned.push_str(c.encode_utf8(&mut [0; 4]));
+
+                    Scanner::Ground
+                }
+
+                (
+                    Scanner::Csi {
+                        mut params,
+                        intermediates,
+                    },
+                    '0'..='?',
+                ) if intermediates.is_empty() => {
+                    params.push(c);
+
+                    Scanner::Csi {
+                        params,
+                        intermediates,
+                    }
+                }
+
+                (
+                    Scanner::Csi {
+                        params,
+                        mut intermediates,
+                    },
+                    ' '..='/',
+                ) => {
+                    intermediates.push(c);
+
+                    Scanner::Csi {
+                        params,
+                        intermediates,
+                    }
+                }
+
+                (
+                    Scanner::Csi {
+                        params,
+                        intermediates,
+                    },
+                    '@'..='~',
+                ) => {
+                    let text = format!("\x1b[{params}{intermediates}{c}");
+                    scanned.csi(&Csi::parse(&text).unwrap());
+
+                    Scanner::Ground
+                }
+
+                // C0 controls are executed in the middle of a sequence.
+                (csi @ Scanner::Csi { .. }, '\0'..='\x1f') => {
+                    scanned.push_str(c.encode_utf8(&mut [0; 4]));
+
+                    csi
+                }
+
+                (held, _) => {
+                    held.push_held(scanned);
+
+                    if c.is_control() {
+                        graphemes.close(scanned);
+                        scanned.push_str(c.encode_utf8(&mut [0; 4]));
+                    } else {
+                        graphemes.print(c, scanned);
+                    }
+
+                    Scanner::Ground
+                }
+            };
+        }
+
+        graphemes.finish(scanned);
+    }
+
+    /// Pass on the start of a sequence held back so far.
+    fn push_held(&self, scanned: &mut Scanned) {
+        match self {
+            Scanner::Ground => {}
+            Scanner::Escape => scanned.push_str("\x1b"),
+
+            Scanner::Csi {
+                params,
+                intermediates,
+            } => {
+                scanned.push_str("\x1b[");
+                scanned.push_str(params);
+                scanned.push_str(intermediates);
+            }
+        }
+    }
+}
+
+/// A complete CSI sequence.
+struct Csi<'a> {
+    text: &'a str,
+    params: &'a str,
+    intermediates: &'a str,
+    c: char,
+}
+
+impl<'a> Csi<'a> {
+    /// The CSI sequence that `data` starts with, when it's complete and
+    /// nothing interrupts it.
+    fn parse(data: &'a str) -> Option<Self> {
+        let body = data.strip_prefix("\x1b[")?;
+        let bytes = body.as_bytes();
+
+        let params = bytes
+            .iter()
+            .position(|b| !(b'0'..=b'?').contains(b))
+            .unwrap_or(bytes.len());
+
+        let intermediates = params
+            + bytes[params..]
+                .iter()
+                .position(|b| !(b' '..=b'/').contains(b))
+                .unwrap_or(bytes.len() - params);
+
+        let c = bytes
+            .get(intermediates)
+            .filter(|b| (b'@'..=b'~').contains(b))
+            .map(|b| char::from(*b))?;
+
+        Some(Csi {
+            text: &data[..intermediates + 3],
+            params: &body[..params],
+            intermediates: &body[params..intermediates],
+            c,
+        })
+    }
+
+    fn is_sgr(&self) -> bool {
+        self.c == 'm'
+            && self.intermediates.is_empty()
+            && !self.params.starts_with(['<', '=', '>', '?'])
+    }
+
+    /// Whether it's an SGR sequence with nothing for avt to misread.
+    fn is_plain_sgr(&self) -> bool {
+        self.is_sgr()
+            && !self.params.contains(':')
+            && !self
+                .params
+                .split(';')
+                .any(|param| param == "58" || param == "59")
+    }
+}
+
+/// The length of the output at the start of `data` that reaches avt as it
+/// is while there are no decorations or clusters to keep track of: printable
+/// ASCII characters, C0 controls, and CSI sequences other than DECSCUSR and
+/// SGR sequences selecting underline styles or colors. Also where the last
+/// character printed in it starts, when nothing comes after it.
+fn verbatim(data: &str) -> (usize, Option<usize>) {
+    let bytes = data.as_bytes();
+    let mut i = 0;
+    let mut last = None;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b' '..=b'~' => {
+                last = Some(i);
+                i += 1;
+            }
+
+            b'\x1b' => {
+                let Some(csi) = Csi::parse(&data[i..]) else {
+                    break;
+                };
+
+                let decscusr = csi.c == 'q' && csi.intermediates == " ";
+
+                if decscusr || csi.is_sgr() && !csi.is_plain_sgr() {
+                    break;
+                }
+
+                last = None;
+                i += csi.text.len();
+            }
+
+            b'\x18' | b'\x1a' | b'\x7f'.. => break,
+
+            _ => {
+                last = None;
+                i += 1;
+            }
+        }
+    }
+
+    (i, last)
+}
+
+/// Split SGR parameters between avt, which misreads underline styles and
+/// colors, and the decoration terminal, which gets the underline style as
+/// an indexed background color and the underline color as the foreground
+/// color.
+fn split_sgr(params: &str) -> (Vec<String>, Vec<String>) {
+    let params: Vec<Vec<u16>> = params
+        .split(';')
+        .map(|param| param.split(':').map(|n| n.parse().unwrap_or(0)).collect())
+        .collect();
+
+    let join = |params: &[Vec<u16>]| {
+        params
+            .iter()
+            .map(|param| {
+                param
+                    .iter()
+                    .map(u16::to_string)
+                    .collect::<Vec<_>>()
+                    .join(":")
+            })
+            .collect::<Vec<_>>()
+            .join(";")
+    };
+
+    let mut vt = Vec::new();
+    let mut decorations = Vec::new();
+    let mut i = 0;
+
+    while i < params.len() {
+        let param = params[i].as_slice();
+        i += 1;
+
+        match param {
+            [0] => {
+                vt.push("0".to_owned());
+                decorations.push("0".to_owned());
+            }
+
+            [4] | [4, 1] => {
+                vt.push("4".to_owned());
+                decorations.push("49".to_owned());
+            }
+
+            [4, 0] | [24] => {
+                vt.push("24".to_owned());
+                decorations.push("49".to_owned());
+            }
+
+            [4, style @ 2..=5] => {
+                vt.push("4".to_owned());
+                decorations.push(format!("48;5;{style}"));
+            }
+
+            [4, ..] => {}
+
+            // The semicolon forms of extended colors take the parameters
+            // after them.
+            [38] | [48] | [58] => {
+                let n = extended_color_len(&params[i..]);
+                let color = join(&params[i - 1..i + n]);
+                i += n;
+
+                if param == [58] {
+                    if n > 0 {
+                        decorations.push(color.replacen("58", "38", 1));
+                    }
+                } else {
+                    vt.push(color);
+                }
+            }
+
+            [58, ..] => decorations.push(join(&[param.to_vec()]).replacen("58", "38", 1)),
+
+            [59] => decorations.push("39".to_owned()),
+
+            _ => vt.push(join(&[param.to_vec()])),
+        }
+    }
+
+    (vt, decorations)
+}
+
+/// Number of parameters following a `38`, `48` or `58` parameter that make up
+/// an extended color.
+fn extended_color_len(params: &[Vec<u16>]) -> usize {
+    let len = match params.first().map(Vec::as_slice) {
+        Some([2]) => 4,
+        Some([5]) => 2,
+        _ => 0,
+    };
+
+    len.min(params.len())
+}
+
+impl Terminal {
+    fn new(vt: Vt, scrollback_limit: Option<usize>) -> Self {
+        Terminal {
+            vt,
+            scrollback_limit,
+            decorations: None,
+            clusters: None,
+            cursor_style: CursorStyle::default(),
+            scanner: Scanner::default(),
+            graphemes: Graphemes::default(),
+            open_col: 0,
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.vt.size()
+    }
+
+    /// A terminal showing what `vt` does, without any SGR sequences.
+    fn copy_without_sgr(&self) -> Vt {
+        let (cols, rows) = self.vt.size();
+        let mut builder = Vt::builder();
+        builder.size(cols, rows);
+
+        if let Some(limit) = self.scrollback_limit {
+            builder.scrollback_limit(limit);
+        }
+
+        let mut vt = builder.build();
+        vt.feed_str(&without_sgr(&self.vt.dump()));
+
+        vt
+    }
+
+    /// The lines of `copy`, started from `vt` later on, lined up with the
+    /// first `count` lines of `vt`, with none for the scrollback it missed.
+    fn lines_of<'a>(
+        &self,
+        copy: &'a Vt,
+        count: usize,
+    ) -> impl Iterator<Item = Option<&'a avt::Line>> {
+        let missing = self.vt.lines().count() - copy.lines().count();
+
+        std::iter::repeat_n(None, missing)
+            .chain(copy.lines().map(Some))
+            .take(count)
+    }
+
+    /// Output that recreates the terminal state, cursor style, underline
+    /// styles and colors, and grapheme clusters included.
+    pub fn dump(&self) -> String {
+        let snapshot = Snapshot::new(self);
+        let mut dump = self.vt.dump();
+
+        if !snapshot.decorations.is_empty() || !snapshot.graphemes.is_empty() {
+            dump = complete_cells(&dump, &snapshot);
+        }
+
+        if let Some(decorations) = &self.decorations {
+            dump.push_str(&pen_decoration(decorations).sgr());
+        }
+
+        if self.cursor_style != CursorStyle::default() {
+            dump.push_str(&format!("\x1b[{} q", self.cursor_style.decscusr()));
+        }
+
+        dump
+    }
+}
+
+/// `dump` without its SGR sequences.
+fn without_sgr(dump: &str) -> String {
+    let mut out = String::with_capacity(dump.len());
+    let mut rest = dump;
+
+    while let Some(start) = rest.find("\x1b[") {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest[2..]
+            .find(|c: char| ('\x40'..='\x7e').contains(&c))
+            .map_or(rest.len(), |end| end + 3);
+
+        if !rest[..end].ends_with('m') {
+            out.push_str(&rest[..end]);
+        }
+
+        rest = &rest[end..];
+    }
+
+    out.push_str(rest);
+
+    out
+}
+
+/// The decoration of the pen that the next characters printed in `vt` get.
+fn pen_decoration(vt: &Vt) -> Decoration {
+    // avt's dump ends by setting the pen, resetting all attributes first.
+    let dump = vt.dump();
+    let sgr = dump.rfind("\x1b[0").map_or("", |i| &dump[i..]);
+    let sgr = sgr.find('m').map_or("", |end| &sgr[..=end]);
+
+    let mut probe = Vt::new(1, 1);
+    probe.feed_str(sgr);
+    probe.feed(' ');
+
+    Decoration::from_pen(probe.line(0).cells()[0].pen())
+}
+
+/// Re-emit what avt's `dump` leaves out of the characters it prints for the
+/// cells of the active screen: the decorations around them, and the rest of
+/// the clusters they start. Those characters come first, or right after the
+/// switch to the alternate screen when that one is active, and are only
+/// interleaved with SGR and REP sequences and line breaks.
+fn complete_cells(dump: &str, snapshot: &Snapshot) -> String {
+    const ALTERNATE: &str = "\x1b[?1047h\x1b[1;1H";
+
+    let start = dump.find(ALTERNATE).map_or(0, |i| i + ALTERNATE.len());
+    let mut out = dump[..start].to_owned();
+    let mut rest = &dump[start..];
+
+    let mut cells = snapshot.lines.iter().enumerate().flat_map(|(row, line)| {
+        line.cells()
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.width() > 0)
+            .map(move |(col, _)| (col, row))
+    });
+
+    let mut open = Decoration::default();
+    let mut last = None;
+
+    // Characters to print: one, or the last one repeated by REP.
+    while let Some(c) = rest.chars().next() {
+        let (len, chars) = match c {
+            '\x1b' => {
+                let Some(params) = rest.strip_prefix("\x1b[") else {
+                    break;
+                };
+
+                let Some(end) = params.find(|c: char| ('\x40'..='\x7e').contains(&c)) else {
+                    break;
+                };
+
+                match (&params[end..end + 1], last) {
+                    ("m", _) => (end + 3, None),
+                    ("b", Some(c)) => match params[..end].parse::<usize>() {
+                        Ok(n) => (end + 3, Some((c, n))),
+                        Err(_) => break,
+                    },
+                    _ => break,
+                }
+            }
+
+            '\r' | '\n' => (1, None),
+            c if c.is_control() => break,
+            c => (c.len_utf8(), Some((c, 1))),
+        };
+
+        let Some((c, n)) = chars else {
+            out.push_str(&open.reset_sgr());
+            open = Decoration::default();
+            out.push_str(&rest[..len]);
+            rest = &rest[len..];
+
+            continue;
+        };
+
+        for _ in 0..n {
+            let Some((col, row)) = cells.next() else {
+                break;
+            };
+
+            let decoration = snapshot.decoration(col, row);
 
-pub fn build(terminal_size: (usize, usize)) -> Vt {
-    Vt::builder()
+            if decoration != open {
+                out.push_str(&open.reset_sgr());
+                out.push_str(&decoration.sgr());
+                open = decoration;
+            }
+
+            match snapshot.grapheme(col, row) {
+                Some(cluster) => out.push_str(cluster),
+                None => out.push(c),
+            }
+        }
+
+        last = Some(c);
+        rest = &rest[len..];
+    }
+
+    out.push_str(&open.reset_sgr());
+    out.push_str(rest);
+
+    out
+}
+
+pub fn build(terminal_size: (usize, usize)) -> Terminal {
+    let vt = Vt::builder()
         .size(terminal_size.0, terminal_size.1)
         .scrollback_limit(0)
-        .build()
+        .build();
+
+    Terminal::new(vt, Some(0))
+}
+
+/// Like `build`, but keeping every line that scrolls off the top.
+pub fn build_with_scrollback(terminal_size: (usize, usize)) -> Terminal {
+    let vt = Vt::builder().size(terminal_size.0, terminal_size.1).build();
+
+    Terminal::new(vt, None)
 }
 
-pub fn feed_str(vt: &mut Vt, data: &str) {
-    vt.feed_str(data);
+/// A point in the output for `vt` to stop feeding it at.
+enum Stop {
+    /// Copy `vt` to start the decoration terminal.
+    Decorations,
+    /// Copy `vt` to start the cluster terminal.
+    Clusters,
+    /// Note the cursor position before the cluster that ends the output.
+    Tail,
+}
+
+pub fn feed_str(terminal: &mut Terminal, data: &str) {
+    let out = || String::with_capacity(data.len());
+
+    let mut scanned = Scanned {
+        vt: out(),
+        decorations: terminal.decorations.as_ref().map(|_| out()),
+        clusters: terminal.clusters.as_ref().map(|_| out()),
+        ..Scanned::default()
+    };
+
+    terminal
+        .scanner
+        .feed(data, &mut terminal.graphemes, &mut scanned);
+
+    if let Some(style) = scanned.cursor_style {
+        terminal.cursor_style = style;
+    }
+
+    let mut stops = Vec::new();
+
+    if terminal.decorations.is_none() && scanned.decorations.is_some() {
+        stops.push((scanned.decorations_from, Stop::Decorations));
+    }
+
+    if terminal.clusters.is_none() && scanned.clusters.is_some() {
+        stops.push((scanned.clusters_from, Stop::Clusters));
+    }
+
+    if let Some(tail) = scanned.tail {
+        stops.push((tail, Stop::Tail));
+    }
+
+    stops.sort_by_key(|(at, _)| *at);
+    let mut fed = 0;
+    let mut before = None;
+
+    for (at, stop) in stops {
+        terminal.vt.feed_str(&scanned.vt[fed..at]);
+        fed = at;
+
+        match stop {
+            Stop::Decorations => terminal.decorations = Some(terminal.copy_without_sgr()),
+            Stop::Clusters => terminal.clusters = Some(terminal.copy_without_sgr()),
+            Stop::Tail => before = Some(terminal.vt.cursor()),
+        }
+    }
+
+    terminal.vt.feed_str(&scanned.vt[fed..]);
+
+    if let (Some(vt), Some(decorations)) = (&mut terminal.decorations, &scanned.decorations) {
+        vt.feed_str(decorations);
+    }
+
+    if let (Some(vt), Some(clusters)) = (&mut terminal.clusters, &scanned.clusters) {
+        if scanned.restamp {
+            vt.feed_str(&format!("\x1b[{}G", terminal.open_col + 1));
+        }
+
+        vt.feed_str(clusters);
+    }
+
+    if let Some(before) = before {
+        let after = terminal.vt.cursor();
+
+        terminal.open_col = if after.row == before.row && after.col > before.col {
+            before.col
+        } else if after.row == before.row && after.col == before.col {
+            // Printed in place at the end of the line, with autowrap off.
+            let cells = terminal.vt.line(after.row).cells();
+            after.col - usize::from(cells[after.col].width() == 0)
+        } else {
+            // Wrapped to the start of the next line.
+            0
+        };
+    }
+}
+
+pub fn resize(terminal: &mut Terminal, (cols, rows): (usize, usize)) {
+    terminal.vt.resize(cols, rows);
+
+    if let Some(decorations) = &mut terminal.decorations {
+        decorations.resize(cols, rows);
+    }
+
+    if let Some(clusters) = &mut terminal.clusters {
+        clusters.resize(cols, rows);
+    }
+
+    terminal.graphemes.open = None;
 }
 
 #[derive(Clone)]
 pub struct Snapshot {
     pub lines: Vec<avt::Line>,
+    /// Decorations of each cell in `lines`, or none when no cell has any.
+    pub decorations: Vec<Vec<Decoration>>,
+    /// Clusters of more than one character in each row of `lines`, by
+    /// column, or none when there are no such clusters.
+    pub graphemes: Vec<Vec<(usize, String)>>,
     pub cursor: Option<(usize, usize)>,
+    pub cursor_style: CursorStyle,
+    /// Whether blinking text is in the hidden phase of its blink.
+    pub blink_hidden: bool,
 }
 
 impl Snapshot {
+    pub fn new(terminal: &Terminal) -> Self {
+        Snapshot {
+            decorations: terminal
+                .decorations
+                .as_ref()
+                .map_or(Vec::new(), |vt| decorations(vt.view().map(Some))),
+            graphemes: terminal.clusters.as_ref().map_or(Vec::new(), |vt| {
+                graphemes(vt.view().map(Some), &terminal.graphemes.clusters)
+            }),
+            cursor_style: terminal.cursor_style,
+            ..Self::from_vt(&terminal.vt)
+        }
+    }
+
+    /// The state of a bare avt terminal, with the default cursor style.
     pub fn from_vt(vt: &Vt) -> Self {
         Snapshot {
             lines: vt.view().cloned().collect(),
+            decorations: Vec::new(),
+            graphemes: Vec::new(),
             cursor: vt.cursor().into(),
+            cursor_style: CursorStyle::default(),
+            blink_hidden: false,
         }
     }
 
+    /// The scrollback followed by the viewport, without trailing blank lines
+    /// and without the cursor.
+    pub fn history(terminal: &Terminal) -> Self {
+        let mut lines: Vec<avt::Line> = terminal.vt.lines().cloned().collect();
+
+        while lines.len() > 1
+            && lines
+                .last()
+                .is_some_and(|line| line.text().trim().is_empty())
+        {
+            lines.pop();
+        }
+
+        Snapshot {
+            decorations: terminal.decorations.as_ref().map_or(Vec::new(), |vt| {
+                decorations(terminal.lines_of(vt, lines.len()))
+            }),
+            graphemes: terminal.clusters.as_ref().map_or(Vec::new(), |vt| {
+                graphemes(
+                    terminal.lines_of(vt, lines.len()),
+                    &terminal.graphemes.clusters,
+                )
+            }),
+            lines,
+            cursor: None,
+            cursor_style: terminal.cursor_style,
+            blink_hidden: false,
+        }
+    }
+
+    /// The decoration of the cell at `col`, `row`.
+    pub fn decoration(&self, col: usize, row: usize) -> Decoration {
+        self.decorations
+            .get(row)
+            .and_then(|line| line.get(col))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The cluster of more than one character at `col`, `row`, if any.
+    pub fn grapheme(&self, col: usize, row: usize) -> Option<&str> {
+        self.graphemes
+            .get(row)?
+            .iter()
+            .find(|(c, _)| *c == col)
+            .map(|(_, cluster)| cluster.as_str())
+    }
+
+    /// The cells of row `row` that characters start in, skipping the tails of
+    /// wide characters, each with its column and text: the character, or the
+    /// whole cluster it starts.
+    pub fn cells(&self, row: usize) -> impl Iterator<Item = (usize, &avt::Cell, String)> {
+        self.lines[row]
+            .cells()
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.width() > 0)
+            .map(move |(col, cell)| {
+                let text = match self.grapheme(col, row) {
+                    Some(cluster) => cluster.to_owned(),
+                    None => cell.char().to_string(),
+                };
+
+                (col, cell, text)
+            })
+    }
+
+    /// The text of row `row`, like `avt::Line::text` but with whole clusters.
+    pub fn text(&self, row: usize) -> String {
+        self.cells(row).map(|(_, _, text)| text).collect()
+    }
+
     pub fn same_visual(&self, other: &Snapshot) -> bool {
-        self.lines == other.lines && self.cursor == other.cursor
+        self.lines == other.lines
+            && self.decorations == other.decorations
+            && self.graphemes == other.graphemes
+            && self.cursor == other.cursor
+            && self.cursor_style == other.cursor_style
+            && self.blink_hidden == other.blink_hidden
+    }
+
+    /// Whether any cell shows blinking text.
+    pub fn has_blinking_text(&self) -> bool {
+        self.lines
+            .iter()
+            .flat_map(|line| line.cells())
+            .any(|cell| cell.pen().is_blink() && cell.char() != ' ')
+    }
+}
+
+fn decorations<'a>(lines: impl Iterator<Item = Option<&'a avt::Line>>) -> Vec<Vec<Decoration>> {
+    let decorations: Vec<Vec<Decoration>> = lines
+        .map(|line| {
+            line.iter()
+                .flat_map(|line| line.cells())
+                .map(|cell| Decoration::from_pen(cell.pen()))
+                .collect()
+        })
+        .collect();
+
+    if decorations
+        .iter()
+        .flatten()
+        .all(|d| *d == Decoration::default())
+    {
+        Vec::new()
+    } else {
+        decorations
+    }
+}
+
+fn graphemes<'a>(
+    lines: impl Iterator<Item = Option<&'a avt::Line>>,
+    clusters: &[String],
+) -> Vec<Vec<(usize, String)>> {
+    let graphemes: Vec<Vec<(usize, String)>> = lines
+        .map(|line| {
+            line.iter()
+                .flat_map(|line| line.cells())
+                .enumerate()
+                .filter_map(|(col, cell)| match cell.pen().foreground() {
+                    Some(avt::Color::RGB(c)) => {
+                        let id = (c.r as usize) << 16 | (c.g as usize) << 8 | c.b as usize;
+
+                        Some((col, clusters.get(id)?.clone()))
+                    }
+
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+
+    if graphemes.iter().all(Vec::is_empty) {
+        Vec::new()
+    } else {
+        graphemes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(data: &[&str]) -> Snapshot {
+        let mut terminal = build((10, 2));
+
+        for data in data {
+            feed_str(&mut terminal, data);
+        }
+
+        Snapshot::new(&terminal)
+    }
+
+    fn cursor_style(data: &[&str]) -> CursorStyle {
+        snapshot(data).cursor_style
+    }
+
+    #[test]
+    fn tracks_decscusr_cursor_style() {
+        assert_eq!(cursor_style(&[]), CursorStyle::default());
+
+        assert_eq!(
+            cursor_style(&["vim\x1b[6 q"]),
+            CursorStyle {
+                shape: CursorShape::Bar,
+                blinking: false,
+            }
+        );
+
+        assert_eq!(
+            cursor_style(&["\x1b[4 q", "\x1b[ q"]),
+            CursorStyle {
+                shape: CursorShape::Block,
+                blinking: true,
+            }
+        );
+    }
+
+    #[test]
+    fn tracks_decscusr_split_between_outputs() {
+        assert_eq!(
+            cursor_style(&["\x1b[", "3", " q"]),
+            CursorStyle {
+                shape: CursorShape::Underline,
+                blinking: true,
+            }
+        );
+    }
+
+    #[test]
+    fn ignores_other_sequences_ending_in_q() {
+        assert_eq!(cursor_style(&["\x1b[5q\x1b[>5 q"]), CursorStyle::default());
+    }
+
+    #[test]
+    fn reset_restores_default_cursor_style() {
+        assert_eq!(cursor_style(&["\x1b[6 q\x1bc"]), CursorStyle::default());
+    }
+
+    #[test]
+    fn tracks_underline_styles_and_colors() {
+        let snapshot =
+            snapshot(&["\x1b[4:3;58:2::255:0:0ma\x1b[24mb\x1b[58;5;1;4:2mc\x1b[59;4md\x1b[0me"]);
+        let cells = snapshot.lines[0].cells();
+
+        assert!(cells[0].pen().is_underline());
+        assert!(!cells[1].pen().is_underline());
+        assert!(cells[2].pen().is_underline());
+        assert!(!cells[2].pen().is_faint());
+        assert!(!cells[4].pen().is_underline());
+
+        assert_eq!(
+            snapshot.decoration(0, 0),
+            Decoration {
+                underline_style: UnderlineStyle::Curly,
+                underline_color: Some(avt::Color::rgb(255, 0, 0)),
+            }
+        );
+
+        assert_eq!(
+            snapshot.decoration(2, 0),
+            Decoration {
+                underline_style: UnderlineStyle::Double,
+                underline_color: Some(avt::Color::Indexed(1)),
+            }
+        );
+
+        assert_eq!(snapshot.decoration(3, 0), Decoration::default());
+        assert_eq!(snapshot.decoration(4, 0), Decoration::default());
+    }
+
+    #[test]
+    fn tracks_underline_style_split_between_outputs() {
+        let snapshot = snapshot(&["\x1b[4:", "4mx"]);
+
+        assert!(snapshot.lines[0].cells()[0].pen().is_underline());
+        assert_eq!(
+            snapshot.decoration(0, 0).underline_style,
+            UnderlineStyle::Dotted
+        );
+    }
+
+    #[test]
+    fn passes_other_sequences_through() {
+        let snapshot = snapshot(&["\x1b[1;38;2;4;5;6mx\x1b[2;3Hy\x1b[?25l"]);
+        let pen = snapshot.lines[0].cells()[0].pen();
+
+        assert!(pen.is_bold());
+        assert!(!pen.is_underline());
+        assert_eq!(pen.foreground(), Some(avt::Color::rgb(4, 5, 6)));
+        assert_eq!(snapshot.lines[1].text().trim(), "y");
+        assert_eq!(snapshot.cursor, None);
+        assert!(snapshot.decorations.is_empty());
+    }
+
+    #[test]
+    fn decorates_cells_from_the_first_underline_style_or_color_on() {
+        let mut terminal = build_with_scrollback((10, 2));
+        feed_str(&mut terminal, "a\r\n\x1b[1;4mb\x1b[4:0;59m\r\n");
+        assert!(terminal.decorations.is_none());
+
+        feed_str(&mut terminal, "c\x1b[4:3;58;5;1md\r\n");
+        let snapshot = Snapshot::history(&terminal);
+
+        assert_eq!(snapshot.lines.len(), 3);
+        assert_eq!(snapshot.decoration(0, 1), Decoration::default());
+        assert_eq!(snapshot.decoration(0, 2), Decoration::default());
+
+        assert_eq!(
+            snapshot.decoration(1, 2),
+            Decoration {
+                underline_style: UnderlineStyle::Curly,
+                underline_color: Some(avt::Color::Indexed(1)),
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_grapheme_clusters_in_one_cell() {
+        let snapshot = snapshot(&["e\u{301}x👨\u{200d}👩\u{200d}👧y👍🏽\u{1f1fa}\u{1f1f8}"]);
+
+        assert_eq!(snapshot.lines[0].text(), "ex👨y👍\u{1f1fa}  ");
+        assert_eq!(snapshot.grapheme(0, 0), Some("e\u{301}"));
+        assert_eq!(snapshot.grapheme(1, 0), None);
+        assert_eq!(snapshot.grapheme(2, 0), Some("👨\u{200d}👩\u{200d}👧"));
+        assert_eq!(snapshot.grapheme(5, 0), Some("👍🏽"));
+        assert_eq!(snapshot.grapheme(7, 0), Some("\u{1f1fa}\u{1f1f8}"));
+        assert_eq!(snapshot.cursor, Some((8, 0)));
+    }
+
+    #[test]
+    fn tracks_grapheme_clusters_split_between_outputs() {
+        let split = snapshot(&["e", "\u{301}", "\u{302}x👨\u{200d}", "👩"]);
+
+        assert_eq!(split.lines[0].text(), "ex👨      ");
+        assert_eq!(split.grapheme(0, 0), Some("e\u{301}\u{302}"));
+        assert_eq!(split.grapheme(2, 0), Some("👨\u{200d}👩"));
+
+        let wrapped = snapshot(&["\x1b[2;9H👨", "\u{200d}👩a\u{301}"]);
+
+        assert_eq!(wrapped.grapheme(8, 0), Some("👨\u{200d}👩"));
+        assert_eq!(wrapped.grapheme(0, 1), Some("a\u{301}"));
+    }
+
+    #[test]
+    fn tracks_grapheme_clusters_from_the_first_one_on() {
+        let mut terminal = build_with_scrollback((10, 2));
+        feed_str(&mut terminal, "a\r\n\x1b[1mb\x1b[0m\r\n\x1b[Kce");
+        assert!(terminal.decorations.is_none());
+        assert!(terminal.clusters.is_none());
+
+        feed_str(&mut terminal, "\u{301}x\r\n");
+        let snapshot = Snapshot::history(&terminal);
+
+        assert_eq!(snapshot.lines.len(), 3);
+        assert_eq!(snapshot.text(2), "ce\u{301}x       ");
+        assert_eq!(snapshot.grapheme(1, 2), Some("e\u{301}"));
+        assert_eq!(snapshot.grapheme(1, 1), None);
+    }
+
+    #[test]
+    fn overwriting_a_cell_drops_its_cluster() {
+        let snapshot = snapshot(&["e\u{301}a\u{301}\u{200d}", "\rx\x1b[K\n\u{301}"]);
+
+        assert_eq!(snapshot.lines[0].text().trim(), "x");
+        assert!(snapshot.graphemes.is_empty());
+    }
+
+    #[test]
+    fn dump_keeps_underline_styles_and_colors() {
+        let mut terminal = build((10, 2));
+        feed_str(
+            &mut terminal,
+            "\x1b[4:3;58;5;1mab\x1b[24mc\r\n\x1b[4;58:2::0:9:9mddddddd",
+        );
+
+        let mut copy = build((10, 2));
+        feed_str(&mut copy, &terminal.dump());
+
+        let (expected, copied) = (Snapshot::new(&terminal), Snapshot::new(&copy));
+
+        assert_eq!(
+            copied.decoration(1, 0),
+            Decoration {
+                underline_style: UnderlineStyle::Curly,
+                underline_color: Some(avt::Color::Indexed(1)),
+            }
+        );
+
+        assert!(copied.lines == expected.lines);
+        assert_eq!(copied.decorations, expected.decorations);
+        assert_eq!(copied.cursor, expected.cursor);
+
+        // The pen keeps its underline color for what follows.
+        feed_str(&mut copy, "e");
+
+        assert_eq!(
+            Snapshot::new(&copy).decoration(7, 1).underline_color,
+            Some(avt::Color::rgb(0, 9, 9))
+        );
+    }
+
+    #[test]
+    fn dump_keeps_underline_styles_on_the_alternate_screen() {
+        let mut terminal = build((10, 2));
+        feed_str(&mut terminal, "\x1b[4:2mab\x1b[?1049h\x1b[Hc\x1b[4:5md");
+
+        let mut copy = build((10, 2));
+        feed_str(&mut copy, &terminal.dump());
+
+        assert_eq!(
+            Snapshot::new(&copy).decoration(1, 0).underline_style,
+            UnderlineStyle::Dashed
+        );
+
+        assert_eq!(
+            Snapshot::new(&copy).decorations,
+            Snapshot::new(&terminal).decorations
+        );
+    }
+
+    #[test]
+    fn dump_keeps_grapheme_clusters() {
+        let mut terminal = build((10, 2));
+        feed_str(
+            &mut terminal,
+            "e\u{301}\x1b[1m👍🏽x\r\n\x1b[4:3ma\u{301}\u{302}",
+        );
+
+        let mut copy = build((10, 2));
+        feed_str(&mut copy, &terminal.dump());
+
+        let copied = Snapshot::new(&copy);
+
+        assert_eq!(copied.text(0).trim_end(), "e\u{301}👍🏽x");
+        assert_eq!(copied.text(1).trim_end(), "a\u{301}\u{302}");
+        assert_eq!(copied.graphemes, Snapshot::new(&terminal).graphemes);
+        assert_eq!(copied.decorations, Snapshot::new(&terminal).decorations);
+    }
+
+    #[test]
+    fn dump_keeps_cursor_style() {
+        let mut terminal = build((10, 2));
+        feed_str(&mut terminal, "\x1b[4 q");
+
+        let mut copy = build((10, 2));
+        feed_str(&mut copy, &terminal.dump());
+
+        assert_eq!(
+            Snapshot::new(&copy).cursor_style,
+            CursorStyle {
+                shape: CursorShape::Underline,
+                blinking: false,
+            }
+        );
     }
 }
@@ -1,6 +1,7 @@
 use anyhow::Result;
 
 use crate::asciicast::Event;
+use crate::terminal;
 
 /// The slice of an adjusted recording timeline needed to resolve selections.
 pub struct Summary {
@@ -82,6 +83,76 @@ pub fn limit_idle_time(
     })
 }
 
+/// Apply `--cols`/`--rows` overrides to resize events, so an overridden
+/// dimension stays fixed for the whole replay.
+pub fn pin_terminal_size(
+    events: impl Iterator<Item = Result<Event>>,
+    cols: Option<usize>,
+    rows: Option<usize>,
+) -> impl Iterator<Item = Result<Event>> {
+    events.map(move |event| {
+        event.map(|e| match e {
+            Event::Resize {
+                time,
+                cols: c,
+                rows: r,
+            } => Event::Resize {
+                time,
+                cols: cols.unwrap_or(c),
+                rows: rows.unwrap_or(r),
+            },
+
+            e => e,
+        })
+    })
+}
+
+/// Cut the timeline down to the `start..=end` range and shift it to start at
+/// zero. Terminal changes before `start` are folded into a synthetic output
+/// event at zero that recreates the terminal state; it's returned along with
+/// the terminal size at `start`.
+pub fn trim(
+    events: &[Event],
+    terminal_size: (usize, usize),
+    start: Option<f64>,
+    end: Option<f64>,
+) -> ((usize, usize), Vec<Event>) {
+    let start = start.unwrap_or(0.0);
+    let mut vt = terminal::build(terminal_size);
+    let mut changed = false;
+    let mut trimmed = Vec::new();
+
+    for event in events {
+        let time = event.time();
+
+        if time < start {
+            match event {
+                Event::Output { data, .. } => terminal::feed_str(&mut vt, data),
+                Event::Resize { cols, rows, .. } => terminal::resize(&mut vt, (*cols, *rows)),
+                _ => continue,
+            }
+
+            changed = true;
+        } else if end.is_none_or(|end| time <= end) {
+            trimmed.push(event.clone().with_time(time - start));
+        } else {
+            break;
+        }
+    }
+
+    if changed {
+        trimmed.insert(
+            0,
+            Event::Output {
+                time: 0.0,
+                data: vt.dump(),
+            },
+        );
+    }
+
+    (vt.size(), trimmed)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::asciicast::Event;
@@ -139,6 +210,31 @@ mod tests {
         assert_eq!(times(events), vec![0.0, 0.5, 1.0]);
     }
 
+    #[test]
+    fn pin_terminal_size_overrides_resize_dimensions() {
+        let events = [
+            output(0.0),
+            Event::Resize {
+                time: 1.0,
+                cols: 100,
+                rows: 40,
+            },
+        ];
+
+        let events = super::pin_terminal_size(events.into_iter().map(Ok), Some(80), None)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            events[1],
+            Event::Resize {
+                time: 1.0,
+                cols: 80,
+                rows: 40
+            }
+        );
+    }
+
     #[test]
     fn limit_idle_time_collapses_long_gaps() {
         let events = [
@@ -175,4 +271,48 @@ mod tests {
 
         assert_eq!(times(events), vec![1.0, 3.0, 5.0]);
     }
+
+    #[test]
+    fn trim_folds_state_before_start_into_initial_output() {
+        let events = [
+            Event::Output {
+                time: 1.0,
+                data: "hello".to_owned(),
+            },
+            Event::Resize {
+                time: 2.0,
+                cols: 20,
+                rows: 5,
+            },
+            output(3.0),
+            Event::Marker {
+                time: 4.0,
+                label: "m".to_owned(),
+            },
+            output(6.0),
+        ];
+
+        let (size, trimmed) = super::trim(&events, (10, 3), Some(2.5), Some(5.0));
+
+        assert_eq!(size, (20, 5));
+        assert_eq!(times(trimmed.clone()), vec![0.0, 0.5, 1.5]);
+
+        let Event::Output { data, .. } = &trimmed[0] else {
+            panic!("expected synthetic output event");
+        };
+
+        let mut vt = avt::Vt::new(20, 5);
+        vt.feed_str(data);
+        assert_eq!(vt.text()[0].trim_end(), "hello");
+    }
+
+    #[test]
+    fn trim_without_prior_changes_only_shifts_times() {
+        let events = [output(1.0), output(2.0)];
+
+        let (size, trimmed) = super::trim(&events, (10, 3), Some(1.0), None);
+
+        assert_eq!(size, (10, 3));
+        assert_eq!(trimmed, vec![output(0.0), output(1.0)]);
+    }
 }